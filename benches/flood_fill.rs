@@ -0,0 +1,20 @@
+use std::time::Instant;
+
+use minesweeper::minesweeper::{FirstClick, GameStatus, Minesweeper, Rules};
+
+const SIZES: [(usize, usize); 3] = [(1000, 1000), (2000, 2000), (3000, 2000)];
+
+fn main() {
+    let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+
+    for (width, height) in SIZES {
+        let mut ms = Minesweeper::with_seed(width, height, width * height / 10_000, rules, 42).unwrap();
+
+        let start = Instant::now();
+        ms.open((width / 2, height / 2)).unwrap();
+        let elapsed = start.elapsed();
+
+        assert_ne!(ms.status(), GameStatus::Lost);
+        println!("{}x{} ({} cells): first click in {:?}", width, height, width * height, elapsed);
+    }
+}
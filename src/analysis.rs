@@ -0,0 +1,348 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::minesweeper::{neighbors, Minesweeper};
+
+/// Difficulty measures of a mine layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardStats {
+    /// Bechtel's Board Benchmark Value: the fewest left clicks that clear
+    /// the board, one per opening and one per number outside of them.
+    pub bbbv: usize,
+    /// Connected areas of fields without neighboring mines.
+    pub openings: usize,
+    /// Connected groups of numbers that no opening reveals.
+    pub islands: usize,
+    /// Clicks, flags and chords included, needed by the greedy ZiNi
+    /// strategy. Never more than `bbbv`.
+    pub zini: usize,
+}
+
+/// Field counts of a layout along with the 3BV unit each field belongs to.
+struct Layout {
+    width: usize,
+    height: usize,
+    wrap_around: bool,
+    mines: Vec<bool>,
+    counts: Vec<u8>,
+    /// Opening of every field without neighboring mines.
+    openings: Vec<Option<usize>>,
+    opening_count: usize,
+    /// Numbers that do not touch any opening and take a click of their own.
+    isolated: Vec<bool>,
+}
+
+impl Minesweeper {
+    /// Measures of the mine layout, or `None` while the mines still wait for
+    /// the first click.
+    pub fn board_stats(&self) -> Option<BoardStats> {
+        if !self.mines_placed {
+            return None;
+        }
+
+        let layout = self.layout();
+
+        Some(BoardStats {
+            bbbv: layout.opening_count + layout.isolated.iter().filter(|&&isolated| isolated).count(),
+            openings: layout.opening_count,
+            islands: layout.islands(),
+            zini: layout.zini(),
+        })
+    }
+
+    /// 3BV already cleared: openings with an open field and open numbers
+    /// outside of any opening.
+    pub(crate) fn solved_bbbv(&self) -> usize {
+        if !self.mines_placed {
+            return 0;
+        }
+
+        let layout = self.layout();
+        let mut openings = vec![false; layout.opening_count];
+        let mut isolated = 0;
+
+        for (x, y) in self.open_fields.iter() {
+            let index = y * self.width + x;
+
+            if let Some(opening) = layout.openings[index] {
+                openings[opening] = true;
+            } else if layout.isolated[index] {
+                isolated += 1;
+            }
+        }
+
+        openings.into_iter().filter(|&solved| solved).count() + isolated
+    }
+
+    fn layout(&self) -> Layout {
+        let mines = (0..self.width * self.height)
+            .map(|index| self.mines.contains(&(index % self.width, index / self.width)))
+            .collect();
+
+        Layout::new(self.width, self.height, self.rules.wrap_around, mines)
+    }
+}
+
+impl Layout {
+    fn new(width: usize, height: usize, wrap_around: bool, mines: Vec<bool>) -> Layout {
+        let mut layout = Layout {
+            width,
+            height,
+            wrap_around,
+            counts: vec![0; mines.len()],
+            openings: vec![None; mines.len()],
+            opening_count: 0,
+            isolated: vec![false; mines.len()],
+            mines,
+        };
+
+        for index in 0..layout.mines.len() {
+            layout.counts[index] = layout.neighbors(index).filter(|&neighbor| layout.mines[neighbor]).count() as u8;
+        }
+
+        let mut seen = vec![false; layout.mines.len()];
+
+        for index in 0..layout.mines.len() {
+            if layout.is_empty(index) && !seen[index] {
+                let id = layout.opening_count;
+                layout.opening_count += 1;
+
+                for field in layout.component(index, &mut seen, |field| layout.is_empty(field)) {
+                    layout.openings[field] = Some(id);
+                }
+            }
+        }
+
+        for index in 0..layout.mines.len() {
+            layout.isolated[index] = !layout.mines[index]
+                && layout.counts[index] > 0
+                && !layout.neighbors(index).any(|neighbor| layout.is_empty(neighbor));
+        }
+
+        layout
+    }
+
+    fn is_empty(&self, index: usize) -> bool {
+        !self.mines[index] && self.counts[index] == 0
+    }
+
+    fn neighbors(&self, index: usize) -> impl Iterator<Item=usize> {
+        let width = self.width;
+
+        neighbors(width, self.height, self.wrap_around, (index % width, index / width)).map(move |(x, y)| y * width + x)
+    }
+
+    /// `start` and every field connected to it through fields matching
+    /// `connects` that is not `seen` yet, marking them as seen.
+    fn component(&self, start: usize, seen: &mut [bool], connects: impl Fn(usize) -> bool) -> Vec<usize> {
+        let mut pending = vec![start];
+        let mut component = Vec::new();
+        seen[start] = true;
+
+        while let Some(index) = pending.pop() {
+            component.push(index);
+
+            for neighbor in self.neighbors(index) {
+                if !seen[neighbor] && connects(neighbor) {
+                    seen[neighbor] = true;
+                    pending.push(neighbor);
+                }
+            }
+        }
+
+        component
+    }
+
+    fn islands(&self) -> usize {
+        let mut seen = vec![false; self.mines.len()];
+        let mut islands = 0;
+
+        for start in 0..self.mines.len() {
+            if self.isolated[start] && !seen[start] {
+                islands += 1;
+                self.component(start, &mut seen, |field| self.isolated[field]);
+            }
+        }
+
+        islands
+    }
+
+    /// Greedy ZiNi: keep chording the number that saves the most clicks,
+    /// then click whatever 3BV is left.
+    fn zini(&self) -> usize {
+        let mut open = vec![false; self.mines.len()];
+        let mut flagged = vec![false; self.mines.len()];
+        let mut premiums = vec![0; self.mines.len()];
+        let mut best = BinaryHeap::new();
+        let mut clicks = 0;
+
+        for (index, premium) in premiums.iter_mut().enumerate() {
+            if !self.mines[index] && self.counts[index] > 0 {
+                *premium = self.premium(index, &open, &flagged);
+                best.push((*premium, Reverse(index)));
+            }
+        }
+
+        // Premiums only change next to fields that get opened or flagged, so
+        // outdated heap entries are skipped instead of rescanning the board.
+        while let Some((premium, Reverse(index))) = best.pop() {
+            if premium <= 0 {
+                break;
+            }
+
+            if premium != premiums[index] {
+                continue;
+            }
+
+            let mut changed = Vec::new();
+
+            if !open[index] {
+                clicks += 1;
+                self.open(index, &mut open, &mut changed);
+            }
+
+            for neighbor in self.neighbors(index) {
+                if self.mines[neighbor] && !flagged[neighbor] {
+                    flagged[neighbor] = true;
+                    changed.push(neighbor);
+                    clicks += 1;
+                } else if !self.mines[neighbor] {
+                    self.open(neighbor, &mut open, &mut changed);
+                }
+            }
+
+            clicks += 1;
+
+            for field in changed {
+                for affected in self.neighbors(field).chain(std::iter::once(field)) {
+                    if !self.mines[affected] && self.counts[affected] > 0 {
+                        let premium = self.premium(affected, &open, &flagged);
+
+                        if premium != premiums[affected] {
+                            premiums[affected] = premium;
+                            best.push((premium, Reverse(affected)));
+                        }
+                    }
+                }
+            }
+        }
+
+        for index in 0..self.mines.len() {
+            if !open[index] && (self.is_empty(index) || self.isolated[index]) {
+                clicks += 1;
+                self.open(index, &mut open, &mut Vec::new());
+            }
+        }
+
+        clicks
+    }
+
+    /// 3BV cleared by chording at `index` minus the clicks it takes.
+    fn premium(&self, index: usize, open: &[bool], flagged: &[bool]) -> isize {
+        let mut units: Vec<usize> = self.neighbors(index)
+            .filter(|&neighbor| !open[neighbor] && !self.mines[neighbor])
+            .filter_map(|neighbor| match self.openings[neighbor] {
+                Some(opening) => Some(opening),
+                None => self.isolated[neighbor].then_some(self.opening_count + neighbor),
+            })
+            .collect();
+
+        units.sort_unstable();
+        units.dedup();
+
+        let flags = self.neighbors(index).filter(|&neighbor| self.mines[neighbor] && !flagged[neighbor]).count();
+        let opening_click = !open[index] && !self.isolated[index];
+
+        units.len() as isize - flags as isize - 1 - opening_click as isize
+    }
+
+    fn open(&self, start: usize, open: &mut [bool], opened: &mut Vec<usize>) {
+        if open[start] {
+            return;
+        }
+
+        open[start] = true;
+        let mut pending = vec![start];
+
+        while let Some(index) = pending.pop() {
+            opened.push(index);
+
+            if self.counts[index] > 0 {
+                continue;
+            }
+
+            for neighbor in self.neighbors(index) {
+                if !open[neighbor] {
+                    open[neighbor] = true;
+                    pending.push(neighbor);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analysis::BoardStats,
+        minesweeper::{FirstClick, Minesweeper, Rules},
+    };
+
+    fn stats(map: &str) -> BoardStats {
+        Minesweeper::from_ascii(map, Rules::default()).unwrap().board_stats().unwrap()
+    }
+
+    #[test]
+    fn check_single_opening() {
+        let stats = stats("\
+            * . .\n\
+            . . .\n\
+            . . .\n");
+
+        assert_eq!(stats, BoardStats { bbbv: 1, openings: 1, islands: 0, zini: 1 });
+    }
+
+    #[test]
+    fn check_isolated_numbers() {
+        assert_eq!(stats("* . *\n"), BoardStats { bbbv: 1, openings: 0, islands: 1, zini: 1 });
+        assert_eq!(stats(". * . * .\n"), BoardStats { bbbv: 3, openings: 0, islands: 3, zini: 3 });
+        assert_eq!(stats(". * . . . * .\n"), BoardStats { bbbv: 3, openings: 1, islands: 2, zini: 3 });
+    }
+
+    #[test]
+    fn check_stats_after_generation() {
+        let rules = Rules { first_click: FirstClick::SafeCell, ..Rules::default() };
+        let mut bbbv = 0;
+        let mut zini = 0;
+
+        for seed in 0..10 {
+            let mut ms = Minesweeper::with_seed(30, 16, 99, rules, seed).unwrap();
+            assert_eq!(ms.board_stats(), None);
+
+            ms.open((0, 0)).unwrap();
+            let stats = ms.board_stats().unwrap();
+
+            assert!(stats.openings + stats.islands <= stats.bbbv);
+            assert!(stats.zini <= stats.bbbv);
+
+            bbbv += stats.bbbv;
+            zini += stats.zini;
+        }
+
+        assert!(zini < bbbv);
+    }
+
+    #[test]
+    fn check_stats_are_saved_with_finished_games() {
+        let mut ms = Minesweeper::from_ascii(". * . . .\n", Rules::default()).unwrap();
+
+        ms.open((4, 0)).unwrap();
+        assert_eq!(ms.save().board_stats, None);
+
+        ms.open((0, 0)).unwrap();
+        assert_eq!(ms.save().board_stats, ms.board_stats());
+        assert_eq!(ms.save().board_stats.unwrap().bbbv, 2);
+    }
+}
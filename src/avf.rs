@@ -0,0 +1,271 @@
+//! Minesweeper Arbiter replays (`.avf`).
+//!
+//! Layout handled here, all integers big-endian:
+//!
+//! - 1 byte format version, then 4 reserved bytes
+//! - 1 byte level: 3 beginner (8x8, 10 mines), 4 intermediate (16x16, 40),
+//!   5 expert (30x16, 99) or 6 custom, followed for custom boards by
+//!   `width - 1` and `height - 1` as bytes and the mine count as `u16`
+//! - one `(row + 1, column + 1)` byte pair per mine
+//! - a bracketed text header of `|` separated fields, such as the date and
+//!   player, whose last field reads `B<3BV>T<seconds>`. Bytes before the
+//!   opening bracket are skipped
+//! - 8 byte mouse records `[buttons, x_hi, hundredths, x_lo, sec_lo, y_hi,
+//!   sec_hi, y_lo]`, where seconds are stored plus one and the record list
+//!   ends with a zero `buttons` byte. `buttons` holds one bit per transition:
+//!   2 left down, 4 left up, 8 right down, 16 right up, 32 middle down and
+//!   64 middle up, with bit 1 always set
+//!
+//! Text sections after the mouse records, such as the skin and the
+//! checksum, are ignored. A header 3BV that disagrees with the mines makes
+//! the file invalid.
+
+use crate::error::FormatError;
+use crate::minesweeper::{Minesweeper, Position, Rules};
+use crate::mouse::{self, MouseEvent, MouseKind, Reader};
+use crate::replay::Replay;
+
+const VERSION: u8 = 1;
+const CUSTOM: u8 = 6;
+const LEVELS: [(u8, (usize, usize, usize)); 3] = [(3, (8, 8, 10)), (4, (16, 16, 40)), (5, (30, 16, 99))];
+
+const BUTTONS: [(u8, MouseKind); 6] = [
+    (2, MouseKind::LeftDown),
+    (8, MouseKind::RightDown),
+    (32, MouseKind::MiddleDown),
+    (4, MouseKind::LeftUp),
+    (16, MouseKind::RightUp),
+    (64, MouseKind::MiddleUp),
+];
+
+pub fn read(bytes: &[u8]) -> Result<Replay, FormatError> {
+    let mut reader = Reader::new(bytes);
+
+    reader.take(5)?;
+
+    let level = reader.u8()?;
+    let (width, height, mine_count) = match LEVELS.iter().find(|(code, _)| *code == level) {
+        Some(&(_, board)) => board,
+        None if level == CUSTOM => (
+            reader.u8()? as usize + 1,
+            reader.u8()? as usize + 1,
+            reader.u16()? as usize,
+        ),
+        None => return Err(FormatError::UnknownLevel(level)),
+    };
+
+    let mut mines = Vec::with_capacity(mine_count);
+
+    for _ in 0..mine_count {
+        let row = reader.u8()?;
+        let column = reader.u8()?;
+
+        if row == 0 || column == 0 {
+            return Err(FormatError::Invalid("mine coordinates start at 1"));
+        }
+
+        mines.push(((column - 1) as usize, (row - 1) as usize));
+    }
+
+    reader.take_until(b'[')?;
+    let header = reader.take_until(b']')?;
+
+    if let Some(bbbv) = header.rsplit(|&byte| byte == b'|').next().and_then(header_bbbv) {
+        let board = Minesweeper::with_mines(width, height, &mines, Rules::default())?;
+
+        if board.board_stats().map(|stats| stats.bbbv) != Some(bbbv) {
+            return Err(FormatError::Invalid("header 3BV does not match the mines"));
+        }
+    }
+
+    let mut events = Vec::new();
+
+    loop {
+        let buttons = reader.u8()?;
+
+        if buttons == 0 {
+            break;
+        }
+
+        let record = [&[buttons][..], reader.take(7)?].concat();
+
+        let x = u16::from_be_bytes([record[1], record[3]]);
+        let y = u16::from_be_bytes([record[5], record[7]]);
+        let seconds = u16::from_be_bytes([record[6], record[4]]).saturating_sub(1) as u64;
+        let time_ms = seconds * 1000 + record[2] as u64 * 10;
+
+        let kinds: Vec<MouseKind> = BUTTONS.iter()
+            .filter(|(bit, _)| buttons & bit != 0)
+            .map(|&(_, kind)| kind)
+            .collect();
+
+        if kinds.is_empty() {
+            events.push(MouseEvent { kind: MouseKind::Move, time_ms, x, y });
+        }
+
+        events.extend(kinds.into_iter().map(|kind| MouseEvent { kind, time_ms, x, y }));
+    }
+
+    mouse::to_replay(width, height, mines, &[], Rules::default(), &events)
+}
+
+pub fn write(replay: &Replay) -> Result<Vec<u8>, FormatError> {
+    let (width, height) = (replay.width, replay.height);
+
+    // Mines are stored one-based in a byte, so the last row and column
+    // must stay below 256.
+    if width > 255 || height > 255 || replay.mines.len() > u16::MAX as usize {
+        return Err(FormatError::Unsupported("boards larger than 255x255"));
+    }
+
+    if !replay.starting_fields.flagged.is_empty() {
+        return Err(FormatError::Unsupported("games that did not start on a blank board"));
+    }
+
+    let mut bytes = vec![VERSION, 0, 0, 0, 0];
+
+    match LEVELS.iter().find(|(_, board)| *board == (width, height, replay.mines.len())) {
+        Some(&(level, _)) => bytes.push(level),
+        None => {
+            bytes.extend([CUSTOM, (width - 1) as u8, (height - 1) as u8]);
+            bytes.extend((replay.mines.len() as u16).to_be_bytes());
+        }
+    }
+
+    for &(x, y) in &replay.mines {
+        bytes.extend(mine_bytes((x, y)));
+    }
+
+    let events = mouse::from_replay(replay)?;
+    let bbbv = Minesweeper::with_mines(width, height, &replay.mines, Rules::default())?
+        .board_stats()
+        .map_or(0, |stats| stats.bbbv);
+    let time_ms = events.last().map_or(0, |event| event.time_ms);
+
+    bytes.extend(format!("[minesweeper|B{}T{}.{:02}]", bbbv, time_ms / 1000, time_ms % 1000 / 10).bytes());
+
+    for event in events {
+        let buttons = BUTTONS.iter()
+            .find(|(_, kind)| *kind == event.kind)
+            .map_or(1, |(bit, _)| 1 | bit);
+
+        let seconds = (event.time_ms / 1000 + 1).min(u16::MAX as u64) as u16;
+        let hundredths = (event.time_ms % 1000 / 10) as u8;
+        let [x_hi, x_lo] = event.x.to_be_bytes();
+        let [y_hi, y_lo] = event.y.to_be_bytes();
+        let [sec_hi, sec_lo] = seconds.to_be_bytes();
+
+        bytes.extend([buttons, x_hi, hundredths, x_lo, sec_lo, y_hi, sec_hi, y_lo]);
+    }
+
+    bytes.extend([0; 8]);
+
+    Ok(bytes)
+}
+
+/// 3BV from a `B<3BV>T<seconds>` header field.
+fn header_bbbv(field: &[u8]) -> Option<usize> {
+    let (bbbv, _) = std::str::from_utf8(field).ok()?.strip_prefix('B')?.split_once('T')?;
+    bbbv.parse().ok()
+}
+
+fn mine_bytes((x, y): Position) -> [u8; 2] {
+    [(y + 1) as u8, (x + 1) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        avf,
+        clock::ManualTime,
+        error::FormatError,
+        minesweeper::{FirstClick, GameStatus, Minesweeper, Rules},
+    };
+
+    #[test]
+    fn check_round_trip() {
+        let time = ManualTime::new(0);
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+        let mut ms = Minesweeper::with_seed(16, 16, 40, rules, 8).unwrap();
+        ms.set_time_source(time.clone());
+
+        ms.open((8, 8)).unwrap();
+        time.advance(1_230);
+
+        let hidden = (0..256)
+            .map(|index| (index % 16, index / 16))
+            .find(|&pos| ms.cell(pos) == Some(crate::snapshot::CellState::Hidden))
+            .unwrap();
+        ms.toggle_flag(hidden).unwrap();
+
+        let replay = ms.replay();
+        let bytes = avf::write(&replay).unwrap();
+
+        assert_eq!(bytes[5], 4);
+
+        let read = avf::read(&bytes).unwrap();
+
+        assert_eq!(read.mines.len(), 40);
+        assert_eq!(read.events, replay.events);
+        assert_eq!(read.status, GameStatus::Playing);
+        assert_eq!(read.verify().unwrap().to_string(), ms.to_string());
+    }
+
+    #[test]
+    fn check_custom_board_and_buttons() {
+        let mut bytes = vec![1, 0, 0, 0, 0, 6, 4, 0, 0, 1, 1, 1];
+        bytes.extend(b"[x]");
+        // Left press and release on (1, 0), then a right press on (0, 0).
+        bytes.extend([3, 0, 0, 24, 1, 0, 0, 8]);
+        bytes.extend([5, 0, 50, 24, 1, 0, 0, 8]);
+        bytes.extend([9, 0, 0, 8, 2, 0, 0, 8]);
+        bytes.extend([0; 8]);
+
+        let replay = avf::read(&bytes).unwrap();
+
+        assert_eq!((replay.width, replay.height), (5, 1));
+        assert_eq!(replay.mines, [(0, 0)]);
+        assert_eq!(replay.events.len(), 2);
+        assert_eq!(replay.events[1].time_ms, 1_000);
+        assert_eq!(replay.status, GameStatus::Playing);
+
+        assert!(matches!(avf::read(&[1, 0, 0, 0, 0, 9]), Err(FormatError::UnknownLevel(9))));
+        assert!(matches!(avf::read(&bytes[..20]), Err(FormatError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn check_header_and_trailing_sections() {
+        let file = |header: &[u8]| {
+            let mut bytes = vec![1, 0, 0, 0, 0, 6, 4, 0, 0, 1, 1, 1];
+            bytes.extend([0x11, 0x22]);
+            bytes.extend(header);
+            // Left press and release on (2, 0), 1.62 seconds in.
+            bytes.extend([3, 0, 62, 40, 2, 0, 0, 8]);
+            bytes.extend([5, 0, 62, 40, 2, 0, 0, 8]);
+            bytes.push(0);
+            bytes.extend(b"Skin: Default\r\ncs=0123456789abcdef\r\n");
+            bytes
+        };
+
+        let replay = avf::read(&file(b"[2023-04-01 12:00:00|Ann|B1T1.62]")).unwrap();
+
+        assert_eq!((replay.width, replay.mines.len()), (5, 1));
+        assert_eq!(replay.events.len(), 1);
+        assert_eq!(replay.events[0].time_ms, 1_620);
+        assert_eq!(replay.status, GameStatus::Won);
+
+        assert!(matches!(avf::read(&file(b"[2023-04-01 12:00:00|Ann|B2T1.62]")), Err(FormatError::Invalid(_))));
+        assert!(matches!(avf::read(&file(b"[2023-04-01 12:00:00")), Err(FormatError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn check_largest_board() {
+        let ms = Minesweeper::with_mines(255, 2, &[(254, 1)], Rules::default()).unwrap();
+        let read = avf::read(&avf::write(&ms.replay()).unwrap()).unwrap();
+
+        assert_eq!((read.width, read.mines), (255, vec![(254, 1)]));
+
+        let ms = Minesweeper::with_mines(256, 1, &[(255, 0)], Rules::default()).unwrap();
+        assert!(matches!(avf::write(&ms.replay()), Err(FormatError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::minesweeper::{GameStatus, Minesweeper};
+
+/// Wall clock used to timestamp moves. Swap it for a `ManualTime` to drive
+/// time by hand in tests.
+pub trait TimeSource: Debug + Send {
+    /// Milliseconds since an arbitrary, fixed origin.
+    fn now_ms(&self) -> u64;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTime;
+
+impl TimeSource for SystemTime {
+    #[cfg(target_arch = "wasm32")]
+    fn now_ms(&self) -> u64 {
+        js_sys::Date::now() as u64
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn now_ms(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64)
+    }
+}
+
+/// Time source that only moves when told to. Clones share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct ManualTime(Arc<AtomicU64>);
+
+impl ManualTime {
+    pub fn new(now_ms: u64) -> ManualTime {
+        ManualTime(Arc::new(AtomicU64::new(now_ms)))
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.0.store(now_ms, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.0.fetch_add(ms, Ordering::Relaxed);
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now_ms(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Play time of a game, counted only while it runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Clock {
+    /// Time counted before the current run.
+    elapsed_ms: u64,
+    /// When the current run began, `None` while stopped or paused.
+    running_since: Option<u64>,
+}
+
+impl Clock {
+    /// A stopped clock showing `elapsed_ms`, e.g. for a loaded game.
+    pub(crate) fn with_elapsed(elapsed_ms: u64) -> Clock {
+        Clock { elapsed_ms, running_since: None }
+    }
+
+    pub(crate) fn elapsed_ms(&self, now_ms: u64) -> u64 {
+        self.elapsed_ms.saturating_add(self.running_since.map_or(0, |since| now_ms.saturating_sub(since)))
+    }
+
+    fn start(&mut self, now_ms: u64) {
+        self.running_since.get_or_insert(now_ms);
+    }
+
+    fn stop(&mut self, now_ms: u64) {
+        self.elapsed_ms = self.elapsed_ms(now_ms);
+        self.running_since = None;
+    }
+}
+
+impl Minesweeper {
+    /// Time played so far. The clock starts with the first open, stops
+    /// when the game is won or lost and does not count pauses.
+    pub fn elapsed_ms(&self) -> u64 {
+        self.clock.elapsed_ms(self.time.now_ms())
+    }
+
+    /// Whether a game in progress has its clock stopped, either by `pause`
+    /// or because it was just loaded. The next move resumes it.
+    pub fn is_paused(&self) -> bool {
+        self.clock_should_run() && self.clock.running_since.is_none()
+    }
+
+    pub fn pause(&mut self) {
+        self.clock.stop(self.time.now_ms());
+    }
+
+    pub fn resume(&mut self) {
+        if self.clock_should_run() {
+            self.clock.start(self.time.now_ms());
+        }
+    }
+
+    /// Brings the clock in line with the game after a move, undo or redo.
+    pub(crate) fn sync_clock(&mut self) {
+        let now = self.time.now_ms();
+
+        if self.status == GameStatus::NotStarted {
+            self.clock = Clock::default();
+        } else if self.clock_should_run() {
+            self.clock.start(now);
+        } else {
+            self.clock.stop(now);
+        }
+    }
+
+    fn clock_should_run(&self) -> bool {
+        self.status == GameStatus::Playing && self.open_fields.len() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::ManualTime,
+        minesweeper::{Minesweeper, Rules},
+    };
+
+    fn board(time: &ManualTime) -> Minesweeper {
+        let mut ms = Minesweeper::from_ascii("\
+            * . . .\n\
+            . . . *\n", Rules::default()).unwrap();
+        ms.set_time_source(time.clone());
+        ms
+    }
+
+    #[test]
+    fn check_clock_runs_from_first_open_until_the_end() {
+        let time = ManualTime::new(1000);
+        let mut ms = board(&time);
+
+        ms.toggle_flag((0, 0)).unwrap();
+        time.advance(500);
+        assert_eq!(ms.elapsed_ms(), 0);
+
+        ms.open((1, 1)).unwrap();
+        time.advance(700);
+        assert_eq!(ms.elapsed_ms(), 700);
+
+        ms.open((3, 0)).unwrap();
+        time.advance(300);
+        ms.open((3, 1)).unwrap();
+        time.advance(5000);
+
+        assert!(ms.status().is_over());
+        assert_eq!(ms.elapsed_ms(), 1000);
+        assert!(!ms.is_paused());
+    }
+
+    #[test]
+    fn check_pause_and_resume() {
+        let time = ManualTime::new(0);
+        let mut ms = board(&time);
+
+        ms.open((1, 1)).unwrap();
+        time.advance(100);
+        ms.pause();
+        time.advance(1000);
+
+        assert!(ms.is_paused());
+        assert_eq!(ms.elapsed_ms(), 100);
+
+        ms.resume();
+        time.advance(50);
+        assert_eq!(ms.elapsed_ms(), 150);
+
+        ms.pause();
+        time.advance(1000);
+        ms.toggle_flag((0, 0)).unwrap();
+        time.advance(50);
+
+        assert!(!ms.is_paused());
+        assert_eq!(ms.elapsed_ms(), 200);
+    }
+
+    #[test]
+    fn check_clock_in_saves_and_snapshots() {
+        let time = ManualTime::new(0);
+        let mut ms = board(&time);
+
+        ms.open((1, 1)).unwrap();
+        time.advance(2500);
+
+        assert_eq!(ms.snapshot().elapsed_ms, 2500);
+        assert_eq!(ms.save().elapsed_ms, 2500);
+
+        let mut restored = Minesweeper::from_json(&ms.to_json()).unwrap();
+        restored.set_time_source(time.clone());
+        time.advance(1000);
+
+        assert!(restored.is_paused());
+        assert_eq!(restored.elapsed_ms(), 2500);
+
+        restored.resume();
+        time.advance(500);
+        assert_eq!(restored.elapsed_ms(), 3000);
+    }
+
+    #[test]
+    fn check_undo_restarts_the_clock() {
+        let time = ManualTime::new(0);
+        let mut ms = board(&time);
+
+        ms.open((1, 1)).unwrap();
+        time.advance(100);
+        ms.open((0, 0)).unwrap();
+        time.advance(100);
+        assert_eq!(ms.elapsed_ms(), 100);
+
+        ms.undo().unwrap();
+        time.advance(100);
+        assert_eq!(ms.elapsed_ms(), 200);
+
+        ms.undo().unwrap();
+        assert_eq!(ms.elapsed_ms(), 0);
+    }
+
+    #[test]
+    fn check_game_moves_to_another_thread() {
+        let time = ManualTime::new(0);
+        let mut ms = board(&time);
+
+        let ms = std::thread::spawn(move || {
+            ms.open((1, 1)).unwrap();
+            ms
+        }).join().unwrap();
+        time.advance(400);
+
+        assert_eq!(ms.elapsed_ms(), 400);
+    }
+}
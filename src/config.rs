@@ -0,0 +1,260 @@
+use crate::error::BoardError;
+use crate::generator::NoGuess;
+use crate::minesweeper::{validate, Chording, FirstClick, Minesweeper, Rules};
+
+/// The classic board sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    /// 9x9 with 10 mines.
+    Beginner,
+    /// 16x16 with 40 mines.
+    Intermediate,
+    /// 30x16 with 99 mines.
+    Expert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mines {
+    Count(usize),
+    /// Share of the fields that hold a mine.
+    Density(f64),
+}
+
+/// Everything needed to deal a new game, checked in one place by
+/// `validate` before `build` creates the board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameConfig {
+    width: usize,
+    height: usize,
+    mines: Mines,
+    rules: Rules,
+    seed: Option<u64>,
+}
+
+impl GameConfig {
+    pub fn new(width: usize, height: usize, mine_count: usize) -> GameConfig {
+        GameConfig {
+            width,
+            height,
+            mines: Mines::Count(mine_count),
+            rules: Rules::default(),
+            seed: None,
+        }
+    }
+
+    /// Preset board with a safe 3x3 start, as in the classic game.
+    pub fn preset(difficulty: Difficulty) -> GameConfig {
+        let (width, height, mine_count) = match difficulty {
+            Difficulty::Beginner => (9, 9, 10),
+            Difficulty::Intermediate => (16, 16, 40),
+            Difficulty::Expert => (30, 16, 99),
+        };
+
+        GameConfig::new(width, height, mine_count).first_click(FirstClick::SafeNeighborhood)
+    }
+
+    pub fn beginner() -> GameConfig {
+        GameConfig::preset(Difficulty::Beginner)
+    }
+
+    pub fn intermediate() -> GameConfig {
+        GameConfig::preset(Difficulty::Intermediate)
+    }
+
+    pub fn expert() -> GameConfig {
+        GameConfig::preset(Difficulty::Expert)
+    }
+
+    pub fn size(mut self, width: usize, height: usize) -> GameConfig {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn mines(mut self, mine_count: usize) -> GameConfig {
+        self.mines = Mines::Count(mine_count);
+        self
+    }
+
+    /// Sizes the mine count to the board, e.g. `0.2` for a fifth of the
+    /// fields, rounded to the nearest whole mine.
+    pub fn density(mut self, density: f64) -> GameConfig {
+        self.mines = Mines::Density(density);
+        self
+    }
+
+    pub fn with_rules(mut self, rules: Rules) -> GameConfig {
+        self.rules = rules;
+        self
+    }
+
+    pub fn first_click(mut self, first_click: FirstClick) -> GameConfig {
+        self.rules.first_click = first_click;
+        self
+    }
+
+    pub fn chording(mut self, chording: Chording) -> GameConfig {
+        self.rules.chording = chording;
+        self
+    }
+
+    pub fn chord_on_open(mut self, chord_on_open: bool) -> GameConfig {
+        self.rules.chord_on_open = chord_on_open;
+        self
+    }
+
+    pub fn undo(mut self, undo: bool) -> GameConfig {
+        self.rules.undo = undo;
+        self
+    }
+
+    pub fn no_guess(mut self, no_guess: Option<NoGuess>) -> GameConfig {
+        self.rules.no_guess = no_guess;
+        self
+    }
+
+    pub fn wrap_around(mut self, wrap_around: bool) -> GameConfig {
+        self.rules.wrap_around = wrap_around;
+        self
+    }
+
+    pub fn question_marks(mut self, question_marks: bool) -> GameConfig {
+        self.rules.question_marks = question_marks;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: Option<u64>) -> GameConfig {
+        self.seed = seed;
+        self
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Number of mines the board gets, with a density resolved against the
+    /// board size.
+    pub fn mine_count(&self) -> usize {
+        match self.mines {
+            Mines::Count(count) => count,
+            Mines::Density(density) => (density * self.width.saturating_mul(self.height) as f64).round() as usize,
+        }
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn validate(&self) -> Result<(), BoardError> {
+        if let Mines::Density(density) = self.mines {
+            if !(0.0..=1.0).contains(&density) {
+                return Err(BoardError::InvalidDensity);
+            }
+        }
+
+        validate(self.width, self.height, self.mine_count(), self.rules)
+    }
+
+    pub fn build(&self) -> Result<Minesweeper, BoardError> {
+        self.validate()?;
+
+        let (width, height, mine_count) = (self.width, self.height, self.mine_count());
+
+        match self.seed {
+            Some(seed) => Minesweeper::with_seed(width, height, mine_count, self.rules, seed),
+            None => Minesweeper::with_rules(width, height, mine_count, self.rules),
+        }
+    }
+}
+
+impl From<Difficulty> for GameConfig {
+    fn from(difficulty: Difficulty) -> Self {
+        GameConfig::preset(difficulty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{Difficulty, GameConfig},
+        error::BoardError,
+        minesweeper::{Chording, FirstClick, GameStatus, Minesweeper, OpenResult},
+        snapshot::CellState,
+    };
+
+    #[test]
+    fn check_presets() {
+        for (difficulty, width, height, mines) in [
+            (Difficulty::Beginner, 9, 9, 10),
+            (Difficulty::Intermediate, 16, 16, 40),
+            (Difficulty::Expert, 30, 16, 99),
+        ] {
+            let ms = GameConfig::from(difficulty).build().unwrap();
+
+            assert_eq!(ms.dimensions(), (width, height));
+            assert_eq!(ms.mine_count(), mines);
+            assert_eq!(ms.rules().first_click, FirstClick::SafeNeighborhood);
+        }
+    }
+
+    #[test]
+    fn check_builder() {
+        let config = GameConfig::expert()
+            .size(20, 10)
+            .density(0.25)
+            .first_click(FirstClick::SafeCell)
+            .chording(Chording::AtLeast)
+            .chord_on_open(true)
+            .undo(false)
+            .wrap_around(true)
+            .question_marks(true)
+            .with_seed(Some(7));
+
+        let ms = config.build().unwrap();
+
+        assert_eq!(config.mine_count(), 50);
+        assert_eq!(ms.mine_count(), 50);
+        assert_eq!(ms.seed(), Some(7));
+        assert_eq!(ms.rules(), config.rules());
+        assert_eq!(config.build().unwrap().to_json(), ms.to_json());
+    }
+
+    #[test]
+    fn check_validation() {
+        assert_eq!(GameConfig::beginner().density(1.5).validate(), Err(BoardError::InvalidDensity));
+        assert_eq!(GameConfig::beginner().density(f64::NAN).validate(), Err(BoardError::InvalidDensity));
+        assert_eq!(GameConfig::beginner().size(0, 9).build().err(), Some(BoardError::ZeroDimension));
+        assert_eq!(
+            GameConfig::beginner().density(1.0).validate(),
+            Err(BoardError::TooManyMinesForSafeStart { mine_count: 81, cell_count: 81 })
+        );
+        assert_eq!(GameConfig::new(3, 3, 9).validate(), Ok(()));
+        assert!(matches!(GameConfig::new(usize::MAX, 3, 0).density(0.5).validate(), Err(BoardError::TooManyCells { .. })));
+    }
+
+    #[test]
+    fn check_wrap_around() {
+        let rules = GameConfig::new(5, 5, 1).wrap_around(true).rules();
+        let mut ms = Minesweeper::from_ascii("\
+            . . . . .\n\
+            . . . . .\n\
+            . . . . .\n\
+            . . . . .\n\
+            . . . . *\n", rules).unwrap();
+
+        let reveal = ms.open((0, 0)).unwrap();
+
+        assert_eq!(reveal.opened, vec![((0, 0), OpenResult::NoMine(1))]);
+        assert_eq!(ms.status(), GameStatus::Playing);
+
+        let reveal = ms.open((2, 2)).unwrap();
+
+        assert_eq!(reveal.status, GameStatus::Won);
+        assert_eq!(ms.cell((3, 0)), Some(CellState::Open(1)));
+        assert_eq!(ms.cell((2, 0)), Some(CellState::Open(0)));
+    }
+}
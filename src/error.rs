@@ -0,0 +1,248 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::minesweeper::{GameStatus, Position, MAX_CELLS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardError {
+    ZeroDimension,
+    /// The board has more than `MAX_CELLS` cells.
+    TooManyCells { width: usize, height: usize },
+    TooManyMines { mine_count: usize, cell_count: usize },
+    TooManyMinesForSafeStart { mine_count: usize, cell_count: usize },
+    MineOutsideBoard(Position),
+    /// Mine density outside of `0.0..=1.0`.
+    InvalidDensity,
+}
+
+impl Display for BoardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardError::ZeroDimension => f.write_str("board width and height must be at least 1"),
+            BoardError::TooManyCells { width, height } => {
+                write!(f, "a {}x{} board has more than the {} cells allowed", width, height, MAX_CELLS)
+            }
+            BoardError::TooManyMines { mine_count, cell_count } => {
+                write!(f, "{} mines do not fit on a board of {} cells", mine_count, cell_count)
+            }
+            BoardError::TooManyMinesForSafeStart { mine_count, cell_count } => {
+                write!(f, "{} mines leave no safe first click on a board of {} cells", mine_count, cell_count)
+            }
+            BoardError::MineOutsideBoard((x, y)) => write!(f, "mine at ({}, {}) is outside of the board", x, y),
+            BoardError::InvalidDensity => f.write_str("mine density must be between 0 and 1"),
+        }
+    }
+}
+
+impl Error for BoardError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    OutOfBounds,
+    GameOver,
+    AlreadyOpen,
+    Flagged,
+    NotOpen,
+    ChordDisabled,
+    UndoDisabled,
+    NothingToUndo,
+    NothingToRedo,
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MoveError::OutOfBounds => "position is outside of the board",
+            MoveError::GameOver => "the game is already over",
+            MoveError::AlreadyOpen => "field is already open",
+            MoveError::Flagged => "field is flagged",
+            MoveError::NotOpen => "field is not open",
+            MoveError::ChordDisabled => "chording is disabled",
+            MoveError::UndoDisabled => "undo is disabled",
+            MoveError::NothingToUndo => "there is no move to undo",
+            MoveError::NothingToRedo => "there is no move to redo",
+        })
+    }
+}
+
+impl Error for MoveError {}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    UnsupportedVersion(u32),
+    Board(BoardError),
+    InvalidState(&'static str),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Json(err) => write!(f, "invalid saved game: {}", err),
+            LoadError::Binary(err) => write!(f, "invalid saved game: {}", err),
+            LoadError::UnsupportedVersion(version) => write!(f, "unsupported save version {}", version),
+            LoadError::Board(err) => write!(f, "invalid saved board: {}", err),
+            LoadError::InvalidState(reason) => write!(f, "invalid saved game: {}", reason),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Json(err) => Some(err),
+            LoadError::Binary(err) => Some(err),
+            LoadError::Board(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(err: serde_json::Error) -> Self {
+        LoadError::Json(err)
+    }
+}
+
+impl From<bincode::Error> for LoadError {
+    fn from(err: bincode::Error) -> Self {
+        LoadError::Binary(err)
+    }
+}
+
+impl From<BoardError> for LoadError {
+    fn from(err: BoardError) -> Self {
+        LoadError::Board(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    Board(BoardError),
+    /// A starting field lies outside of the board.
+    FieldOutsideBoard(Position),
+    Move { index: usize, error: MoveError },
+    OutcomeMismatch { expected: GameStatus, actual: GameStatus },
+    /// The outcome matches, but not the number of open fields.
+    OpenFieldsMismatch { expected: usize, actual: usize },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Json(err) => write!(f, "invalid replay: {}", err),
+            ReplayError::UnsupportedVersion(version) => write!(f, "unsupported replay version {}", version),
+            ReplayError::Board(err) => write!(f, "invalid replay board: {}", err),
+            ReplayError::FieldOutsideBoard((x, y)) => write!(f, "starting field ({}, {}) is outside of the board", x, y),
+            ReplayError::Move { index, error } => write!(f, "replay event {} failed: {}", index, error),
+            ReplayError::OutcomeMismatch { expected, actual } => {
+                write!(f, "replay ends {:?} instead of the recorded {:?}", actual, expected)
+            }
+            ReplayError::OpenFieldsMismatch { expected, actual } => {
+                write!(f, "replay ends with {} open fields instead of the recorded {}", actual, expected)
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Json(err) => Some(err),
+            ReplayError::Board(err) => Some(err),
+            ReplayError::Move { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(err: serde_json::Error) -> Self {
+        ReplayError::Json(err)
+    }
+}
+
+impl From<BoardError> for ReplayError {
+    fn from(err: BoardError) -> Self {
+        ReplayError::Board(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    UnexpectedEof,
+    BadMagic,
+    UnknownLevel(u8),
+    Invalid(&'static str),
+    Unsupported(&'static str),
+    Board(BoardError),
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::UnexpectedEof => f.write_str("replay file ends unexpectedly"),
+            FormatError::BadMagic => f.write_str("not a replay file of this format"),
+            FormatError::UnknownLevel(level) => write!(f, "unknown level {}", level),
+            FormatError::Invalid(reason) => write!(f, "invalid replay file: {}", reason),
+            FormatError::Unsupported(feature) => write!(f, "{} are not supported by this format", feature),
+            FormatError::Board(err) => write!(f, "invalid replay board: {}", err),
+        }
+    }
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FormatError::Board(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<BoardError> for FormatError {
+    fn from(err: BoardError) -> Self {
+        FormatError::Board(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutError {
+    InvalidCharacter(char),
+    Truncated,
+    UnsupportedVersion(u8),
+    RaggedRow { line: usize },
+    UnexpectedCharacter { line: usize, found: char },
+    Board(BoardError),
+}
+
+impl Display for LayoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutError::InvalidCharacter(c) => write!(f, "invalid character {:?} in layout code", c),
+            LayoutError::Truncated => f.write_str("layout code is truncated"),
+            LayoutError::UnsupportedVersion(version) => write!(f, "unsupported layout code version {}", version),
+            LayoutError::RaggedRow { line } => write!(f, "line {} does not have as many fields as the first row", line),
+            LayoutError::UnexpectedCharacter { line, found } => write!(f, "unexpected {:?} on line {}", found, line),
+            LayoutError::Board(err) => write!(f, "invalid layout: {}", err),
+        }
+    }
+}
+
+impl Error for LayoutError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LayoutError::Board(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<BoardError> for LayoutError {
+    fn from(err: BoardError) -> Self {
+        LayoutError::Board(err)
+    }
+}
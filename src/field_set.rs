@@ -0,0 +1,70 @@
+use crate::minesweeper::Position;
+
+/// Set of board positions backed by one flag per cell.
+///
+/// Mirrors the parts of the `HashSet` API the board needs, but lookups are a
+/// plain index so flood filling millions of cells stays cheap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FieldSet {
+    width: usize,
+    fields: Vec<bool>,
+    len: usize,
+}
+
+impl FieldSet {
+    pub(crate) fn new(width: usize, height: usize) -> FieldSet {
+        FieldSet {
+            width,
+            fields: vec![false; width * height],
+            len: 0,
+        }
+    }
+
+    fn index(&self, (x, y): Position) -> Option<usize> {
+        if x < self.width {
+            Some(y * self.width + x).filter(|&index| index < self.fields.len())
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn contains(&self, pos: &Position) -> bool {
+        self.index(*pos).is_some_and(|index| self.fields[index])
+    }
+
+    pub(crate) fn insert(&mut self, pos: Position) -> bool {
+        let index = self.index(pos).expect("position outside of the board");
+        let inserted = !self.fields[index];
+
+        if inserted {
+            self.fields[index] = true;
+            self.len += 1;
+        }
+
+        inserted
+    }
+
+    pub(crate) fn remove(&mut self, pos: &Position) -> bool {
+        match self.index(*pos) {
+            Some(index) if self.fields[index] => {
+                self.fields[index] = false;
+                self.len -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item=Position> + '_ {
+        let width = self.width;
+
+        self.fields.iter()
+            .enumerate()
+            .filter(|(_, &set)| set)
+            .map(move |(index, _)| (index % width, index / width))
+    }
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+use crate::field_set::FieldSet;
+use crate::minesweeper::{GameStatus, Mark, Minesweeper, Position, Rules};
+
+/// Settings for boards that can be cleared without guessing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoGuess {
+    /// Mine layouts to try before settling for `fallback`.
+    pub attempts: u32,
+    pub fallback: Fallback,
+}
+
+impl Default for NoGuess {
+    fn default() -> Self {
+        NoGuess {
+            attempts: 100,
+            fallback: Fallback::default(),
+        }
+    }
+}
+
+/// Board to deal when no attempt could be solved, which gets likely on
+/// dense boards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Fallback {
+    /// The attempt that left the fewest fields to guess.
+    #[default]
+    FewestGuesses,
+    /// The last attempt, as random as a board without `NoGuess`.
+    Random,
+}
+
+impl Minesweeper {
+    /// Places the mines for a first click on `start`, retrying until the
+    /// solver clears the board when `Rules::no_guess` asks for it.
+    pub(crate) fn generate(&mut self, start: Position, safe: &[Position]) {
+        let Some(no_guess) = self.rules.no_guess else {
+            self.place_mines(safe);
+            return;
+        };
+
+        let mut best: Option<(usize, FieldSet)> = None;
+
+        for _ in 0..no_guess.attempts.max(1) {
+            self.mines = FieldSet::new(self.width, self.height);
+            self.place_mines(safe);
+
+            let undecided = self.undecided_fields(start);
+
+            if undecided == 0 {
+                return;
+            }
+
+            if best.as_ref().is_none_or(|(fewest, _)| undecided < *fewest) {
+                best = Some((undecided, self.mines.clone()));
+            }
+        }
+
+        if let (Fallback::FewestGuesses, Some((_, mines))) = (no_guess.fallback, best) {
+            self.mines = mines;
+        }
+    }
+
+    /// Safe fields the solver cannot reach when the game starts on `start`,
+    /// opening every field it proves safe and flagging every mine.
+    pub(crate) fn undecided_fields(&self, start: Position) -> usize {
+        let mines: Vec<Position> = self.mines.iter().collect();
+        let rules = Rules { undo: false, wrap_around: self.rules.wrap_around, ..Rules::default() };
+        let mut game = Minesweeper::with_mines(self.width, self.height, &mines, rules)
+            .expect("mines come from a valid board");
+
+        if game.open(start).is_ok() {
+            while game.status == GameStatus::Playing {
+                let deductions = game.deductions();
+
+                if deductions.is_empty() {
+                    break;
+                }
+
+                for deduction in deductions {
+                    if deduction.mine {
+                        game.marks.insert(deduction.position, Mark::Flag);
+                    } else if !game.open_fields.contains(&deduction.position) && game.open(deduction.position).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.width * self.height - self.mine_count - game.open_fields.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        generator::{Fallback, NoGuess},
+        minesweeper::{FirstClick, GameStatus, Mark, Minesweeper, Rules},
+    };
+
+    #[test]
+    fn check_boards_are_solvable() {
+        let rules = Rules {
+            first_click: FirstClick::SafeNeighborhood,
+            no_guess: Some(NoGuess::default()),
+            ..Rules::default()
+        };
+
+        for seed in 0..10 {
+            let mut ms = Minesweeper::with_seed(16, 16, 40, rules, seed).unwrap();
+            ms.open((3, 12)).unwrap();
+
+            assert_eq!(ms.mines.len(), 40);
+            assert_eq!(ms.undecided_fields((3, 12)), 0);
+        }
+    }
+
+    #[test]
+    fn check_wrapped_boards_are_solvable() {
+        let rules = Rules {
+            first_click: FirstClick::SafeNeighborhood,
+            no_guess: Some(NoGuess::default()),
+            wrap_around: true,
+            ..Rules::default()
+        };
+
+        for seed in 0..20 {
+            let mut ms = Minesweeper::with_seed(10, 10, 20, rules, seed).unwrap();
+            ms.open((4, 4)).unwrap();
+
+            while ms.status() == GameStatus::Playing {
+                let deductions = ms.deductions();
+                assert!(!deductions.is_empty(), "seed {} needs a guess", seed);
+
+                for deduction in deductions {
+                    if deduction.mine {
+                        ms.marks.insert(deduction.position, Mark::Flag);
+                    } else if !ms.open_fields.contains(&deduction.position) {
+                        ms.open(deduction.position).unwrap();
+                    }
+                }
+            }
+
+            assert_eq!(ms.status(), GameStatus::Won);
+        }
+    }
+
+    #[test]
+    fn check_unprotected_start_is_safe() {
+        let rules = Rules { no_guess: Some(NoGuess::default()), ..Rules::default() };
+
+        for seed in 0..10 {
+            let mut ms = Minesweeper::with_seed(8, 8, 10, rules, seed).unwrap();
+
+            assert!(ms.mines.iter().next().is_none());
+            assert_ne!(ms.open((0, 0)).unwrap().status, GameStatus::Lost);
+        }
+    }
+
+    #[test]
+    fn check_fallback_on_dense_boards() {
+        for fallback in [Fallback::FewestGuesses, Fallback::Random] {
+            let rules = Rules {
+                first_click: FirstClick::SafeNeighborhood,
+                no_guess: Some(NoGuess { attempts: 5, fallback }),
+                ..Rules::default()
+            };
+
+            let mut ms = Minesweeper::with_seed(9, 9, 60, rules, 1).unwrap();
+            let reveal = ms.open((4, 4)).unwrap();
+
+            assert_eq!(ms.mines.len(), 60);
+            assert_eq!(reveal.status, GameStatus::Playing);
+        }
+    }
+}
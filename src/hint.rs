@@ -0,0 +1,245 @@
+use crate::minesweeper::{Minesweeper, Position};
+use crate::snapshot::{CellState, Snapshot};
+use crate::solver::{Deduction, Reason};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HintKind {
+    Safe,
+    Mine,
+    /// Nothing is certain, this is the field least likely to be a mine.
+    Guess { probability: f64 },
+}
+
+/// Suggested next move together with the reasoning behind it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    pub position: Position,
+    pub kind: HintKind,
+    pub explanation: String,
+}
+
+impl Minesweeper {
+    /// Suggests the next move and counts it in `hints`. Returns `None` once
+    /// the game is over.
+    pub fn hint(&mut self) -> Option<Hint> {
+        if self.status.is_over() {
+            return None;
+        }
+
+        self.hints += 1;
+
+        if !self.mines_placed {
+            let position = (self.width / 2, self.height / 2);
+
+            return Some(Hint {
+                position,
+                kind: HintKind::Safe,
+                explanation: format!("the first click is always safe, so {} is safe", name(position)),
+            });
+        }
+
+        let snapshot = self.snapshot();
+
+        if let Some(deduction) = self.deductions().first() {
+            return Some(Hint {
+                position: deduction.position,
+                kind: if deduction.mine { HintKind::Mine } else { HintKind::Safe },
+                explanation: explain(&snapshot, self.mine_count, deduction),
+            });
+        }
+
+        let probabilities = self.mine_probabilities();
+        let (index, &probability) = probabilities.iter()
+            .enumerate()
+            .filter(|&(index, _)| snapshot.cells[index].is_hidden())
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+
+        let position = (index % self.width, index / self.width);
+
+        Some(Hint {
+            position,
+            kind: HintKind::Guess { probability },
+            explanation: format!(
+                "no field can be proven safe, {} has the lowest chance of a mine at {:.0}%",
+                name(position),
+                probability * 100.0
+            ),
+        })
+    }
+
+    /// Number of times `hint` was asked this game.
+    pub fn hints(&self) -> usize {
+        self.hints
+    }
+}
+
+fn explain(snapshot: &Snapshot, mine_count: usize, deduction: &Deduction) -> String {
+    let target = name(deduction.position);
+    let verdict = if deduction.mine { "a mine" } else { "safe" };
+
+    match deduction.reason {
+        Reason::Single { cell } => {
+            let number = number(snapshot, cell);
+            let (flags, hidden) = neighbors(snapshot, cell);
+
+            if deduction.mine {
+                format!(
+                    "the {} at {} needs {} more {} and has only {} hidden {} left, so {} is a mine",
+                    number, name(cell), number - flags, plural(number - flags, "mine"), hidden, plural(hidden, "neighbor"), target
+                )
+            } else {
+                format!("the {} at {} already touches {} {}, so {} is safe", number, name(cell), flags, plural(flags, "flag"), target)
+            }
+        }
+        Reason::Subset { inner, outer } => {
+            let extra = remaining(snapshot, outer) - remaining(snapshot, inner);
+            let needs = if extra == 0 {
+                "no more mines".to_string()
+            } else {
+                format!("{} more {}", extra, plural(extra, "mine"))
+            };
+
+            format!(
+                "the {} at {} needs {} than the {} at {}, whose hidden neighbors it all touches, so {} is {}",
+                number(snapshot, outer), name(outer), needs, number(snapshot, inner), name(inner), target, verdict
+            )
+        }
+        Reason::Overlap { cell, other } => format!(
+            "the {} at {} shares hidden neighbors with the {} at {}, which limits how many of its {} {} can lie elsewhere, so {} is {}",
+            number(snapshot, cell),
+            name(cell),
+            number(snapshot, other),
+            name(other),
+            remaining(snapshot, cell),
+            plural(remaining(snapshot, cell), "mine"),
+            target,
+            verdict
+        ),
+        Reason::MineCount if deduction.mine => format!("only mines are left to uncover, so {} is a mine", target),
+        Reason::MineCount => format!("all {} mines are flagged, so {} is safe", mine_count, target),
+    }
+}
+
+fn name((x, y): Position) -> String {
+    format!("({},{})", x, y)
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        word.to_string()
+    } else {
+        format!("{}s", word)
+    }
+}
+
+fn number(snapshot: &Snapshot, cell: Position) -> usize {
+    match snapshot.get(cell) {
+        Some(CellState::Open(count)) => count as usize,
+        _ => 0,
+    }
+}
+
+/// Flagged and hidden neighbors of `cell`.
+fn neighbors(snapshot: &Snapshot, cell: Position) -> (usize, usize) {
+    snapshot.neighbors(cell).fold((0, 0), |(flags, hidden), pos| match snapshot.get(pos) {
+        Some(CellState::Flagged) => (flags + 1, hidden),
+        Some(cell) if cell.is_hidden() => (flags, hidden + 1),
+        _ => (flags, hidden),
+    })
+}
+
+fn remaining(snapshot: &Snapshot, cell: Position) -> usize {
+    number(snapshot, cell).saturating_sub(neighbors(snapshot, cell).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hint::HintKind,
+        minesweeper::{FirstClick, GameStatus, Minesweeper, Rules},
+    };
+
+    fn board(map: &str) -> Minesweeper {
+        Minesweeper::from_ascii(map, Rules::default()).unwrap()
+    }
+
+    #[test]
+    fn check_safe_hint() {
+        let mut ms = board("\
+            F o .\n\
+            . . .\n");
+
+        let hint = ms.hint().unwrap();
+
+        assert_eq!(hint.position, (2, 0));
+        assert_eq!(hint.kind, HintKind::Safe);
+        assert_eq!(hint.explanation, "the 1 at (1,0) already touches 1 flag, so (2,0) is safe");
+        assert_eq!(ms.hints(), 1);
+    }
+
+    #[test]
+    fn check_mine_hint() {
+        let mut ms = board("\
+            o o o\n\
+            o o o\n\
+            o * .\n");
+
+        let hint = ms.hint().unwrap();
+
+        assert_eq!(hint.position, (1, 2));
+        assert_eq!(hint.kind, HintKind::Mine);
+        assert_eq!(hint.explanation, "the 1 at (0,1) needs 1 more mine and has only 1 hidden neighbor left, so (1,2) is a mine");
+    }
+
+    #[test]
+    fn check_subset_hint() {
+        let mut ms = board("\
+            o o o\n\
+            o o o\n\
+            . * .\n");
+
+        let hint = ms.hint().unwrap();
+
+        assert_eq!(hint.position, (2, 2));
+        assert_eq!(hint.kind, HintKind::Safe);
+        assert_eq!(
+            hint.explanation,
+            "the 1 at (1,1) needs no more mines than the 1 at (0,1), whose hidden neighbors it all touches, so (2,2) is safe"
+        );
+    }
+
+    #[test]
+    fn check_guess_hint() {
+        let mut ms = board("\
+            o o\n\
+            * .\n\
+            . *\n");
+
+        let hint = ms.hint().unwrap();
+
+        match hint.kind {
+            HintKind::Guess { probability } => {
+                assert_eq!(hint.position, (0, 1));
+                assert!((probability - 0.5).abs() < 1e-9);
+                assert!(hint.explanation.contains("lowest chance"));
+            }
+            kind => panic!("unexpected {:?}", kind),
+        }
+
+        assert_eq!(ms.hints(), 1);
+    }
+
+    #[test]
+    fn check_first_click_and_finished_games() {
+        let rules = Rules { first_click: FirstClick::SafeCell, ..Rules::default() };
+        let mut ms = Minesweeper::with_seed(9, 9, 10, rules, 5).unwrap();
+
+        let hint = ms.hint().unwrap();
+        assert_eq!(hint.kind, HintKind::Safe);
+        assert_ne!(ms.open(hint.position).unwrap().status, GameStatus::Lost);
+
+        let mut ms = board("X .\n");
+        assert_eq!(ms.hint(), None);
+        assert_eq!(ms.hints(), 0);
+    }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::MoveError;
+use crate::minesweeper::{GameStatus, Minesweeper, Position};
+use crate::replay::ReplayAction;
+
+/// A player command as it was applied to the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Open(Position),
+    Chord(Position),
+    ToggleFlag(Position),
+}
+
+/// An applied action together with its effects, so it can be taken back or
+/// applied again without rerunning any game logic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Move {
+    pub(crate) action: Action,
+    pub(crate) opened: Vec<Position>,
+    pub(crate) status_before: GameStatus,
+    pub(crate) status_after: GameStatus,
+}
+
+impl Minesweeper {
+    pub(crate) fn record(&mut self, mv: Move) {
+        self.record_event(ReplayAction::Play(mv.action));
+        self.moves += 1;
+        self.redo_log.clear();
+        self.move_log.push(mv);
+        self.sync_clock();
+    }
+
+    /// Actions applied so far, oldest first, without the undone ones.
+    pub fn move_log(&self) -> impl Iterator<Item=Action> + '_ {
+        self.move_log.iter().map(|mv| mv.action)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.rules.undo && !self.move_log.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.rules.undo && !self.redo_log.is_empty()
+    }
+
+    /// Takes back the last action, including every field its cascade
+    /// opened, and returns it. Mines laid out by the first open stay where
+    /// they are, so undoing it cannot deal a new board.
+    pub fn undo(&mut self) -> Result<Action, MoveError> {
+        if !self.rules.undo {
+            return Err(MoveError::UndoDisabled);
+        }
+
+        let mv = self.move_log.pop().ok_or(MoveError::NothingToUndo)?;
+
+        for pos in &mv.opened {
+            self.open_fields.remove(pos);
+        }
+
+        if let Action::ToggleFlag(pos) = mv.action {
+            self.cycle_mark(pos, true);
+        }
+
+        self.status = mv.status_before;
+        self.moves -= 1;
+
+        let action = mv.action;
+        self.redo_log.push(mv);
+        self.record_event(ReplayAction::Undo);
+        self.sync_clock();
+
+        Ok(action)
+    }
+
+    /// Applies the most recently undone action again and returns it.
+    pub fn redo(&mut self) -> Result<Action, MoveError> {
+        if !self.rules.undo {
+            return Err(MoveError::UndoDisabled);
+        }
+
+        let mv = self.redo_log.pop().ok_or(MoveError::NothingToRedo)?;
+
+        for &pos in &mv.opened {
+            self.open_fields.insert(pos);
+        }
+
+        if let Action::ToggleFlag(pos) = mv.action {
+            self.cycle_mark(pos, false);
+        }
+
+        self.status = mv.status_after;
+        self.moves += 1;
+
+        let action = mv.action;
+        self.move_log.push(mv);
+        self.record_event(ReplayAction::Redo);
+        self.sync_clock();
+
+        Ok(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::ManualTime,
+        error::MoveError,
+        history::Action,
+        minesweeper::{FirstClick, GameStatus, Mark, Minesweeper, Rules},
+        save::SavedGame,
+        stats::Clicks,
+    };
+
+    /// Saved state without the clicks, which undo does not take back.
+    fn board(ms: &Minesweeper) -> SavedGame {
+        SavedGame { clicks: Clicks::default(), ..ms.save() }
+    }
+
+    #[test]
+    fn check_undo_cascade_and_first_click() {
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+        let mut ms = Minesweeper::with_seed(10, 10, 10, rules, 3).unwrap();
+        ms.set_time_source(ManualTime::new(0));
+        let initial = board(&ms);
+
+        ms.open((5, 5)).unwrap();
+        let opened = board(&ms);
+        let layout = ms.layout_code(false);
+
+        assert_eq!(ms.undo(), Ok(Action::Open((5, 5))));
+        assert_eq!(board(&ms), SavedGame { mines: opened.mines.clone(), ..initial });
+        assert_eq!(ms.status(), GameStatus::NotStarted);
+
+        assert_eq!(ms.redo(), Ok(Action::Open((5, 5))));
+        assert_eq!(board(&ms), opened);
+        assert_eq!(ms.redo(), Err(MoveError::NothingToRedo));
+
+        // Undoing the first click must not deal a new board.
+        ms.undo().unwrap();
+        ms.open((5, 5)).unwrap();
+        assert_eq!(board(&ms), opened);
+        assert_eq!(ms.layout_code(false), layout);
+
+        ms.undo().unwrap();
+        ms.open((0, 9)).unwrap();
+        assert_eq!(ms.layout_code(false), layout);
+    }
+
+    #[test]
+    fn check_undo_loss_and_flags() {
+        let mut ms = Minesweeper::from_ascii("* . . . * .\n", Rules::default()).unwrap();
+        ms.set_time_source(ManualTime::new(0));
+
+        ms.open((2, 0)).unwrap();
+        ms.toggle_flag((4, 0)).unwrap();
+        let before_loss = board(&ms);
+
+        ms.open((0, 0)).unwrap();
+        assert_eq!(ms.status(), GameStatus::Lost);
+
+        assert_eq!(ms.undo(), Ok(Action::Open((0, 0))));
+        assert_eq!(board(&ms), before_loss);
+        assert_eq!(ms.status(), GameStatus::Playing);
+
+        assert_eq!(ms.undo(), Ok(Action::ToggleFlag((4, 0))));
+        assert_eq!(ms.mark((4, 0)), None);
+
+        ms.toggle_flag((0, 0)).unwrap();
+        assert!(!ms.can_redo());
+        assert_eq!(ms.move_log().collect::<Vec<_>>(), [Action::Open((2, 0)), Action::ToggleFlag((0, 0))]);
+    }
+
+    #[test]
+    fn check_undo_disabled() {
+        let rules = Rules { undo: false, ..Rules::default() };
+        let mut ms = Minesweeper::with_rules(5, 5, 0, rules).unwrap();
+
+        ms.toggle_flag((1, 1)).unwrap();
+
+        assert!(!ms.can_undo());
+        assert_eq!(ms.undo(), Err(MoveError::UndoDisabled));
+        assert_eq!(ms.mark((1, 1)), Some(Mark::Flag));
+    }
+}
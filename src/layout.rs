@@ -0,0 +1,334 @@
+use crate::error::LayoutError;
+use crate::field_set::FieldSet;
+use crate::minesweeper::{GameStatus, Mark, Minesweeper, Position, Rules};
+use crate::replay::StartingFields;
+
+const CODE_VERSION: u8 = 1;
+const WITH_OPEN_FIELDS: u8 = 1;
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+impl Minesweeper {
+    /// URL-safe code for the mine layout, and optionally the open fields,
+    /// that `from_layout_code` turns back into the same board. `None` while
+    /// mines are still waiting for the first click.
+    ///
+    /// The code is base64url without padding over a version byte, a flags
+    /// byte, the width and height as LEB128 varints and one bitmap per
+    /// included field set, row by row with the lowest bit first.
+    pub fn layout_code(&self, with_open_fields: bool) -> Option<String> {
+        if !self.mines_placed {
+            return None;
+        }
+
+        let mut bytes = vec![CODE_VERSION, if with_open_fields { WITH_OPEN_FIELDS } else { 0 }];
+        write_varint(&mut bytes, self.width);
+        write_varint(&mut bytes, self.height);
+        write_bitmap(&mut bytes, &self.mines, self.width, self.height);
+
+        if with_open_fields {
+            write_bitmap(&mut bytes, &self.open_fields, self.width, self.height);
+        }
+
+        Some(encode(&bytes))
+    }
+
+    pub fn from_layout_code(code: &str, rules: Rules) -> Result<Minesweeper, LayoutError> {
+        let bytes = decode(code)?;
+        let mut bytes = bytes.iter().copied();
+        let mut next = || bytes.next().ok_or(LayoutError::Truncated);
+
+        let version = next()?;
+
+        if version != CODE_VERSION {
+            return Err(LayoutError::UnsupportedVersion(version));
+        }
+
+        let flags = next()?;
+        let width = read_varint(&mut next)?;
+        let height = read_varint(&mut next)?;
+        let cell_count = width.checked_mul(height).ok_or(LayoutError::Truncated)?;
+
+        let mut read_bitmap = || -> Result<Vec<Position>, LayoutError> {
+            let mut fields = Vec::new();
+            let mut byte = 0;
+
+            for index in 0..cell_count {
+                if index % 8 == 0 {
+                    byte = next()?;
+                }
+
+                if byte >> (index % 8) & 1 == 1 {
+                    fields.push((index % width, index / width));
+                }
+            }
+
+            Ok(fields)
+        };
+
+        let mines = read_bitmap()?;
+        let open_fields = if flags & WITH_OPEN_FIELDS != 0 { read_bitmap()? } else { vec![] };
+
+        let mut minesweeper = Minesweeper::with_mines(width, height, &mines, rules)?;
+        minesweeper.preset_fields(&open_fields, &[], &[]);
+
+        Ok(minesweeper)
+    }
+
+    /// Builds a board from a hand-drawn map, one line per row:
+    ///
+    /// - `.` hidden field, `*` hidden mine
+    /// - `o` open field, `X` open mine (a lost game)
+    /// - `F` flagged mine, `f` flag on a field without a mine
+    /// - `Q` question-marked mine, `q` question mark on a field without a mine
+    ///
+    /// Spaces between fields and blank lines are ignored.
+    pub fn from_ascii(map: &str, rules: Rules) -> Result<Minesweeper, LayoutError> {
+        let (mut mines, mut open_fields, mut flagged, mut questioned) = (vec![], vec![], vec![], vec![]);
+        let (mut width, mut height) = (None, 0);
+
+        for (line, row) in map.lines().enumerate() {
+            let fields: Vec<char> = row.chars().filter(|c| !c.is_whitespace()).collect();
+
+            if fields.is_empty() {
+                continue;
+            }
+
+            if *width.get_or_insert(fields.len()) != fields.len() {
+                return Err(LayoutError::RaggedRow { line: line + 1 });
+            }
+
+            for (x, &field) in fields.iter().enumerate() {
+                let pos = (x, height);
+
+                match field {
+                    '.' => {}
+                    '*' => mines.push(pos),
+                    'o' => open_fields.push(pos),
+                    'X' => {
+                        mines.push(pos);
+                        open_fields.push(pos);
+                    }
+                    'F' => {
+                        mines.push(pos);
+                        flagged.push(pos);
+                    }
+                    'f' => flagged.push(pos),
+                    'Q' => {
+                        mines.push(pos);
+                        questioned.push(pos);
+                    }
+                    'q' => questioned.push(pos),
+                    found => return Err(LayoutError::UnexpectedCharacter { line: line + 1, found }),
+                }
+            }
+
+            height += 1;
+        }
+
+        let mut minesweeper = Minesweeper::with_mines(width.unwrap_or(0), height, &mines, rules)?;
+        minesweeper.preset_fields(&open_fields, &flagged, &questioned);
+
+        Ok(minesweeper)
+    }
+
+    /// Map of the board in the format read by `from_ascii`, mines included.
+    pub fn to_ascii(&self) -> String {
+        let mut map = String::with_capacity((self.width + 1) * self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pos = (x, y);
+                let mine = self.mines.contains(&pos);
+
+                map.push(match (mine, self.open_fields.contains(&pos), self.mark(pos)) {
+                    (true, true, _) => 'X',
+                    (false, true, _) => 'o',
+                    (true, false, Some(Mark::Flag)) => 'F',
+                    (false, false, Some(Mark::Flag)) => 'f',
+                    (true, false, Some(Mark::Question)) => 'Q',
+                    (false, false, Some(Mark::Question)) => 'q',
+                    (true, false, None) => '*',
+                    (false, false, None) => '.',
+                });
+            }
+
+            map.push('\n');
+        }
+
+        map
+    }
+
+    /// Marks fields as open, flagged or question-marked without cascading,
+    /// and derives the game status from them. Replays start from these
+    /// fields.
+    pub(crate) fn preset_fields(&mut self, open_fields: &[Position], flagged: &[Position], questioned: &[Position]) {
+        let hit_mine = open_fields.iter().any(|pos| self.mines.contains(pos));
+
+        for &pos in open_fields {
+            self.open_fields.insert(pos);
+        }
+
+        self.marks.extend(flagged.iter().map(|&pos| (pos, Mark::Flag)));
+        self.marks.extend(questioned.iter().map(|&pos| (pos, Mark::Question)));
+        self.starting_fields = StartingFields {
+            open: open_fields.to_vec(),
+            flagged: flagged.to_vec(),
+            questioned: questioned.to_vec(),
+        };
+
+        if hit_mine {
+            self.status = GameStatus::Lost;
+        } else if !open_fields.is_empty() {
+            self.status = if self.is_cleared() { GameStatus::Won } else { GameStatus::Playing };
+        } else if !flagged.is_empty() || !questioned.is_empty() {
+            self.status = GameStatus::Playing;
+        }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    bytes.push(value as u8);
+}
+
+fn read_varint(next: &mut impl FnMut() -> Result<u8, LayoutError>) -> Result<usize, LayoutError> {
+    let mut value = 0usize;
+
+    for shift in (0..usize::BITS).step_by(7) {
+        let byte = next()?;
+        value |= ((byte & 0x7f) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(LayoutError::Truncated)
+}
+
+fn write_bitmap(bytes: &mut Vec<u8>, fields: &FieldSet, width: usize, height: usize) {
+    let start = bytes.len();
+    bytes.resize(start + (width * height).div_ceil(8), 0);
+
+    for (x, y) in fields.iter() {
+        let index = y * width + x;
+        bytes[start + index / 8] |= 1 << (index % 8);
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut code = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| group | (byte as u32) << (16 - 8 * i));
+
+        for i in 0..=chunk.len() {
+            code.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+
+    code
+}
+
+fn decode(code: &str) -> Result<Vec<u8>, LayoutError> {
+    let mut bytes = Vec::with_capacity(code.len() * 3 / 4);
+    let (mut group, mut bits) = (0u32, 0);
+
+    for c in code.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c).ok_or(LayoutError::InvalidCharacter(c as char))?;
+        group = group << 6 | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::{BoardError, LayoutError},
+        layout::{decode, encode},
+        minesweeper::{FirstClick, GameStatus, Minesweeper, Rules},
+    };
+
+    #[test]
+    fn check_base64() {
+        for len in 0..10 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 97 + 13) as u8).collect();
+            assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+        }
+
+        assert_eq!(encode(b"Man"), "TWFu");
+        assert_eq!(encode(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn check_layout_round_trip() {
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+        let mut ms = Minesweeper::with_seed(30, 16, 99, rules, 21).unwrap();
+
+        assert_eq!(ms.layout_code(false), None);
+
+        ms.open((10, 10)).unwrap();
+
+        let code = ms.layout_code(false).unwrap();
+        assert!(code.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'));
+
+        let shared = Minesweeper::from_layout_code(&code, Rules::default()).unwrap();
+        assert_eq!(shared.mines, ms.mines);
+        assert_eq!(shared.mine_count(), 99);
+        assert_eq!(shared.status(), GameStatus::NotStarted);
+
+        let resumed = Minesweeper::from_layout_code(&ms.layout_code(true).unwrap(), Rules::default()).unwrap();
+        assert_eq!(resumed.to_string(), ms.to_string());
+        assert_eq!(resumed.status(), ms.status());
+    }
+
+    #[test]
+    fn check_invalid_codes() {
+        assert_eq!(Minesweeper::from_layout_code("AQ*", Rules::default()).err(), Some(LayoutError::InvalidCharacter('*')));
+        assert_eq!(Minesweeper::from_layout_code("AQAF", Rules::default()).err(), Some(LayoutError::Truncated));
+        assert_eq!(Minesweeper::from_layout_code("Ag", Rules::default()).err(), Some(LayoutError::UnsupportedVersion(2)));
+    }
+
+    #[test]
+    fn check_ascii_round_trip() {
+        let map = "\
+            o o o . *\n\
+            o o o F q\n\
+            * f . Q .\n";
+
+        let ms = Minesweeper::from_ascii(map, Rules::default()).unwrap();
+
+        assert_eq!(ms.dimensions(), (5, 3));
+        assert_eq!(ms.mine_count(), 4);
+        assert_eq!(ms.status(), GameStatus::Playing);
+        assert_eq!(ms.to_ascii(), "ooo.*\noooFq\n*f.Q.\n");
+        assert_eq!(Minesweeper::from_ascii(&ms.to_ascii(), Rules::default()).unwrap().save(), ms.save());
+    }
+
+    #[test]
+    fn check_invalid_ascii() {
+        assert_eq!(
+            Minesweeper::from_ascii("..\n...", Rules::default()).err(),
+            Some(LayoutError::RaggedRow { line: 2 })
+        );
+        assert_eq!(
+            Minesweeper::from_ascii("..\n.?", Rules::default()).err(),
+            Some(LayoutError::UnexpectedCharacter { line: 2, found: '?' })
+        );
+        assert_eq!(
+            Minesweeper::from_ascii("\n", Rules::default()).err(),
+            Some(LayoutError::Board(BoardError::ZeroDimension))
+        );
+    }
+}
@@ -1,30 +1,459 @@
-mod random;
-mod minesweeper;
-
-use std::cell::RefCell;
+pub mod analysis;
+pub mod avf;
+pub mod clock;
+pub mod config;
+pub mod error;
+mod field_set;
+pub mod generator;
+pub mod hint;
+pub mod history;
+pub mod layout;
+pub mod random;
+pub mod replay;
+pub mod rmv;
+pub mod save;
+pub mod minesweeper;
+pub mod probability;
+mod mouse;
+pub mod snapshot;
+pub mod solver;
+pub mod stats;
 
+use config::{Difficulty, GameConfig};
+use generator::NoGuess;
+use hint::HintKind;
 use minesweeper::*;
+use replay::Replay;
+use snapshot::CellState;
 use wasm_bindgen::prelude::*;
 
-thread_local! {
-    static MINESWEEPER:RefCell<Minesweeper> = RefCell::new(Minesweeper::new(10,10,5)) ;
+/// Codes used by `Game.cells` for fields that are not open numbers, matching
+/// the constants on `CellState`.
+#[wasm_bindgen]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy)]
+pub enum Cell {
+    Hidden = 9,
+    Flagged = 10,
+    Mine = 11,
+    ExplodedMine = 12,
+    WrongFlag = 13,
+    Questioned = 14,
+}
+
+/// The classic board sizes for `Game.preset`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub enum Preset {
+    Beginner,
+    Intermediate,
+    Expert,
+}
+
+/// Rule options for a new `Game`. Every option defaults to the classic
+/// behavior: a safe 3x3 start and chording on exact flag counts.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct GameOptions {
+    #[wasm_bindgen(js_name = safeFirstClick)]
+    pub safe_first_click: bool,
+    #[wasm_bindgen(js_name = safeNeighborhood)]
+    pub safe_neighborhood: bool,
+    pub chording: bool,
+    #[wasm_bindgen(js_name = exactChord)]
+    pub exact_chord: bool,
+    #[wasm_bindgen(js_name = chordOnOpen)]
+    pub chord_on_open: bool,
+    pub undo: bool,
+    /// Deals only boards that can be solved without guessing.
+    #[wasm_bindgen(js_name = noGuess)]
+    pub no_guess: bool,
+    #[wasm_bindgen(js_name = wrapAround)]
+    pub wrap_around: bool,
+    /// Right clicks cycle through flag, question mark and hidden.
+    #[wasm_bindgen(js_name = questionMarks)]
+    pub question_marks: bool,
+    pub seed: Option<u64>,
+}
+
+#[wasm_bindgen]
+impl GameOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GameOptions {
+        GameOptions::default()
+    }
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            safe_first_click: true,
+            safe_neighborhood: true,
+            chording: true,
+            exact_chord: true,
+            chord_on_open: false,
+            undo: true,
+            no_guess: false,
+            wrap_around: false,
+            question_marks: false,
+            seed: None,
+        }
+    }
+}
+
+impl GameOptions {
+    fn apply(&self, config: GameConfig) -> GameConfig {
+        config.with_rules(self.rules()).with_seed(self.seed)
+    }
+
+    fn from_rules(rules: Rules) -> GameOptions {
+        GameOptions {
+            safe_first_click: rules.first_click != FirstClick::Unprotected,
+            safe_neighborhood: rules.first_click == FirstClick::SafeNeighborhood,
+            chording: rules.chording != Chording::Disabled,
+            exact_chord: rules.chording != Chording::AtLeast,
+            chord_on_open: rules.chord_on_open,
+            undo: rules.undo,
+            no_guess: rules.no_guess.is_some(),
+            wrap_around: rules.wrap_around,
+            question_marks: rules.question_marks,
+            seed: None,
+        }
+    }
+
+    fn rules(&self) -> Rules {
+        Rules {
+            first_click: match (self.safe_first_click, self.safe_neighborhood) {
+                (false, _) => FirstClick::Unprotected,
+                (true, false) => FirstClick::SafeCell,
+                (true, true) => FirstClick::SafeNeighborhood,
+            },
+            chording: match (self.chording, self.exact_chord) {
+                (false, _) => Chording::Disabled,
+                (true, true) => Chording::Exact,
+                (true, false) => Chording::AtLeast,
+            },
+            chord_on_open: self.chord_on_open,
+            undo: self.undo,
+            no_guess: self.no_guess.then(NoGuess::default),
+            wrap_around: self.wrap_around,
+            question_marks: self.question_marks,
+        }
+    }
+}
+
+/// Next move suggested by `Game.hint`. `kind` is `"safe"`, `"mine"` or
+/// `"guess"`, and `probability` the chance of a mine under the field.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct GameHint {
+    pub x: usize,
+    pub y: usize,
+    pub kind: String,
+    pub probability: f64,
+    pub explanation: String,
+}
+
+/// Report on a finished game from `Game.stats`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct GameStats {
+    #[wasm_bindgen(js_name = elapsedMs)]
+    pub elapsed_ms: f64,
+    #[wasm_bindgen(js_name = leftClicks)]
+    pub left_clicks: usize,
+    #[wasm_bindgen(js_name = rightClicks)]
+    pub right_clicks: usize,
+    #[wasm_bindgen(js_name = chordClicks)]
+    pub chord_clicks: usize,
+    #[wasm_bindgen(js_name = wastedClicks)]
+    pub wasted_clicks: usize,
+    pub hints: usize,
+    pub bbbv: usize,
+    #[wasm_bindgen(js_name = solvedBbbv)]
+    pub solved_bbbv: usize,
+    #[wasm_bindgen(js_name = bbbvPerSecond)]
+    pub bbbv_per_second: f64,
+    pub ioe: f64,
+    pub correctness: f64,
+    pub throughput: f64,
+}
+
+#[wasm_bindgen]
+pub struct Game {
+    minesweeper: Minesweeper,
+    options: GameOptions,
+}
+
+#[wasm_bindgen]
+impl Game {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: usize, height: usize, mines: usize, options: Option<GameOptions>) -> Result<Game, JsError> {
+        let options = options.unwrap_or_default();
+
+        Ok(Game {
+            minesweeper: new_minesweeper(width, height, mines, &options)?,
+            options,
+        })
+    }
+
+    /// Beginner (9x9, 10 mines), Intermediate (16x16, 40) or Expert (30x16,
+    /// 99) board with the given rules.
+    pub fn preset(preset: Preset, options: Option<GameOptions>) -> Result<Game, JsError> {
+        let difficulty = match preset {
+            Preset::Beginner => Difficulty::Beginner,
+            Preset::Intermediate => Difficulty::Intermediate,
+            Preset::Expert => Difficulty::Expert,
+        };
+        let (width, height) = GameConfig::from(difficulty).dimensions();
+        let mines = GameConfig::from(difficulty).mine_count();
+
+        Game::new(width, height, mines, options)
+    }
+
+    /// Board with `density` of its fields mined, e.g. `0.2` for a fifth.
+    #[wasm_bindgen(js_name = withDensity)]
+    pub fn with_density(width: usize, height: usize, density: f64, options: Option<GameOptions>) -> Result<Game, JsError> {
+        let options = options.unwrap_or_default();
+
+        Ok(Game {
+            minesweeper: options.apply(GameConfig::new(width, height, 0).density(density)).build()?,
+            options,
+        })
+    }
+
+    /// Starts a game on the board shared through `layoutCode`.
+    #[wasm_bindgen(js_name = fromLayoutCode)]
+    pub fn from_layout_code(code: &str, options: Option<GameOptions>) -> Result<Game, JsError> {
+        let options = options.unwrap_or_default();
+
+        Ok(Game {
+            minesweeper: Minesweeper::from_layout_code(code, options.rules())?,
+            options,
+        })
+    }
+
+    /// URL-safe code of the mine layout, or `undefined` before the first
+    /// click of a game with a safe start.
+    #[wasm_bindgen(js_name = layoutCode)]
+    pub fn layout_code(&self, with_open_fields: bool) -> Option<String> {
+        self.minesweeper.layout_code(with_open_fields)
+    }
+
+    /// Restores a game written by `save`, e.g. from `localStorage`.
+    pub fn load(json: &str) -> Result<Game, JsError> {
+        Ok(Game::from_minesweeper(Minesweeper::from_json(json)?))
+    }
+
+    #[wasm_bindgen(js_name = loadBytes)]
+    pub fn load_bytes(bytes: &[u8]) -> Result<Game, JsError> {
+        Ok(Game::from_minesweeper(Minesweeper::from_bytes(bytes)?))
+    }
+
+    /// Recording of every action so far as a replay JSON document.
+    pub fn replay(&self) -> String {
+        self.minesweeper.replay().to_json()
+    }
+
+    pub fn save(&self) -> String {
+        self.minesweeper.to_json()
+    }
+
+    #[wasm_bindgen(js_name = saveBytes)]
+    pub fn save_bytes(&self) -> Vec<u8> {
+        self.minesweeper.to_bytes()
+    }
+
+    /// Starts over with the same settings and, unless a seed was given in
+    /// the options, a fresh mine layout.
+    pub fn reset(&mut self) -> Result<(), JsError> {
+        let (width, height) = self.minesweeper.dimensions();
+        let mines = self.minesweeper.mine_count();
+        self.minesweeper = new_minesweeper(width, height, mines, &self.options)?;
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn moves(&self) -> usize {
+        self.minesweeper.moves()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.minesweeper.dimensions().0
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.minesweeper.dimensions().1
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn seed(&self) -> Option<u64> {
+        self.minesweeper.seed()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> String {
+        status_name(self.minesweeper.status()).to_string()
+    }
+
+    /// Play time in milliseconds, see `Minesweeper::elapsed_ms`.
+    #[wasm_bindgen(getter, js_name = elapsedMs)]
+    pub fn elapsed_ms(&self) -> f64 {
+        self.minesweeper.elapsed_ms() as f64
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn paused(&self) -> bool {
+        self.minesweeper.is_paused()
+    }
+
+    pub fn pause(&mut self) {
+        self.minesweeper.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.minesweeper.resume();
+    }
+
+    /// Cell codes row by row: `0..=8` for open fields, `Cell` for the rest.
+    pub fn cells(&self) -> Vec<u8> {
+        self.minesweeper.snapshot().codes()
+    }
+
+    /// Returns the opened fields as flat `[x, y, value]` triples, where
+    /// `value` is the neighboring mine count or `Cell.ExplodedMine` for a
+    /// mine, the same codes as `cells`.
+    pub fn open(&mut self, x: usize, y: usize) -> Result<Vec<u32>, JsError> {
+        let reveal = self.minesweeper.open((x, y))?;
+        Ok(reveal_triples(&reveal))
+    }
+
+    /// Same encoding as `open`; returns an empty array when the adjacent
+    /// flags do not allow chording.
+    pub fn chord(&mut self, x: usize, y: usize) -> Result<Vec<u32>, JsError> {
+        match self.minesweeper.chord((x, y))? {
+            Chord::Revealed(reveal) => Ok(reveal_triples(&reveal)),
+            Chord::FlagMismatch { .. } => Ok(vec![]),
+        }
+    }
+
+    /// Chance of a mine under every field row by row, for a heat map.
+    /// Open fields are `0` and flags `1`.
+    #[wasm_bindgen(js_name = mineProbabilities)]
+    pub fn mine_probabilities(&self) -> Vec<f64> {
+        self.minesweeper.mine_probabilities()
+    }
+
+    /// Suggests the next move, or `undefined` once the game is over.
+    pub fn hint(&mut self) -> Option<GameHint> {
+        let hint = self.minesweeper.hint()?;
+        let (kind, probability) = match hint.kind {
+            HintKind::Safe => ("safe", 0.0),
+            HintKind::Mine => ("mine", 1.0),
+            HintKind::Guess { probability } => ("guess", probability),
+        };
+
+        Some(GameHint {
+            x: hint.position.0,
+            y: hint.position.1,
+            kind: kind.to_string(),
+            probability,
+            explanation: hint.explanation,
+        })
+    }
+
+    /// Efficiency report, or `undefined` until the game is over.
+    pub fn stats(&self) -> Option<GameStats> {
+        let stats = self.minesweeper.stats()?;
+
+        Some(GameStats {
+            elapsed_ms: stats.elapsed_ms as f64,
+            left_clicks: stats.clicks.left,
+            right_clicks: stats.clicks.right,
+            chord_clicks: stats.clicks.chord,
+            wasted_clicks: stats.clicks.wasted,
+            hints: stats.hints,
+            bbbv: stats.bbbv,
+            solved_bbbv: stats.solved_bbbv,
+            bbbv_per_second: stats.bbbv_per_second,
+            ioe: stats.ioe,
+            correctness: stats.correctness,
+            throughput: stats.throughput,
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn hints(&self) -> usize {
+        self.minesweeper.hints()
+    }
+
+    #[wasm_bindgen(js_name = toggleFlag)]
+    pub fn toggle_flag(&mut self, x: usize, y: usize) -> Result<bool, JsError> {
+        Ok(self.minesweeper.toggle_flag((x, y))?)
+    }
+
+    #[wasm_bindgen(getter, js_name = canUndo)]
+    pub fn can_undo(&self) -> bool {
+        self.minesweeper.can_undo()
+    }
+
+    #[wasm_bindgen(getter, js_name = canRedo)]
+    pub fn can_redo(&self) -> bool {
+        self.minesweeper.can_redo()
+    }
+
+    pub fn undo(&mut self) -> Result<(), JsError> {
+        self.minesweeper.undo()?;
+        Ok(())
+    }
+
+    pub fn redo(&mut self) -> Result<(), JsError> {
+        self.minesweeper.redo()?;
+        Ok(())
+    }
+}
+
+/// Replays a recording from `Game.replay` and returns the status it ends
+/// with, or throws when it does not lead to the recorded outcome.
+#[wasm_bindgen(js_name = verifyReplay)]
+pub fn verify_replay(json: &str) -> Result<String, JsError> {
+    let game = Replay::from_json(json)?.verify()?;
+    Ok(status_name(game.status()).to_string())
 }
 
-#[wasm_bindgen(js_name = getState)]
-pub fn get_state() -> String {
-    MINESWEEPER.with(|ms| ms.borrow().to_string())
+impl Game {
+    fn from_minesweeper(minesweeper: Minesweeper) -> Game {
+        Game {
+            options: GameOptions::from_rules(minesweeper.rules()),
+            minesweeper,
+        }
+    }
 }
 
-#[wasm_bindgen(js_name = openField)]
-pub fn open_field(x: usize, y: usize) {
-    MINESWEEPER.with(|ms| {
-        ms.borrow_mut().open((x, y));
-    });
+fn new_minesweeper(width: usize, height: usize, mines: usize, options: &GameOptions) -> Result<Minesweeper, JsError> {
+    Ok(options.apply(GameConfig::new(width, height, mines)).build()?)
 }
 
-#[wasm_bindgen(js_name = toggleFlag)]
-pub fn toggle_flag(x: usize, y: usize) {
-    MINESWEEPER.with(|ms| {
-        ms.borrow_mut().toggle_flag((x, y));
-    });
-}
\ No newline at end of file
+fn reveal_triples(reveal: &Reveal) -> Vec<u32> {
+    reveal.opened.iter()
+        .flat_map(|&((x, y), result)| {
+            let value = match result {
+                OpenResult::Mine => CellState::EXPLODED_MINE as u32,
+                OpenResult::NoMine(count) => count as u32,
+            };
+
+            [x as u32, y as u32, value]
+        })
+        .collect()
+}
+
+fn status_name(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::NotStarted => "notStarted",
+        GameStatus::Playing => "playing",
+        GameStatus::Won => "won",
+        GameStatus::Lost => "lost",
+    }
+}
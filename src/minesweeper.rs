@@ -1,49 +1,164 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::fmt::{Display, Write};
 
-use crate::random::random_range;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{Clock, SystemTime, TimeSource};
+use crate::error::{BoardError, MoveError};
+use crate::field_set::FieldSet;
+use crate::generator::NoGuess;
+use crate::history::{Action, Move};
+use crate::random::{random_seed, MineRng, SeededRng};
+use crate::replay::{ReplayEvent, StartingFields};
+use crate::snapshot::{CellState, Snapshot};
+use crate::stats::Clicks;
 
 pub type Position = (usize, usize);
 
+/// Largest number of cells a board may have, which keeps every per-cell
+/// buffer well within the memory of a 32-bit wasm module.
+pub const MAX_CELLS: usize = 1 << 26;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpenResult {
     Mine,
     NoMine(u8),
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chord {
+    Revealed(Reveal),
+    /// The adjacent flags do not allow chording, so nothing was opened.
+    FlagMismatch { flags: u8, mines: u8 },
+}
+
+/// Everything that changed during a single `open`, including fields opened
+/// by a cascade or a chord, in the order they were opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reveal {
+    pub opened: Vec<(Position, OpenResult)>,
+    pub hit_mine: bool,
+    pub status: GameStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameStatus {
+    NotStarted,
+    Playing,
+    Won,
+    Lost,
+}
+
+impl GameStatus {
+    pub fn is_over(self) -> bool {
+        matches!(self, GameStatus::Won | GameStatus::Lost)
+    }
+}
+
+/// Controls how the first `open` of a game is protected from mines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FirstClick {
+    /// Mines are placed up front, so the first click can hit one.
+    #[default]
+    Unprotected,
+    /// Mines are placed on the first click, never under the clicked cell.
+    SafeCell,
+    /// Like `SafeCell`, but also keeps the 3x3 neighborhood clear whenever
+    /// the board has room for the requested mines elsewhere.
+    SafeNeighborhood,
+}
+
+/// Decides when `chord` opens the hidden neighbors of an open field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Chording {
+    Disabled,
+    /// Chords only when the adjacent flags match the field's count exactly.
+    #[default]
+    Exact,
+    /// Chords when at least as many flags as the field's count are adjacent.
+    AtLeast,
+}
+
+/// What a player put on a hidden field with `toggle_flag`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Flag,
+    /// Reminder to come back to a field. It can still be opened and does
+    /// not count as a flag when chording.
+    Question,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    pub first_click: FirstClick,
+    pub chording: Chording,
+    /// Makes `open` on an already open field chord, like a simultaneous
+    /// left+right click, instead of failing with `AlreadyOpen`.
+    pub chord_on_open: bool,
+    /// Allows `undo` and `redo`. Turn it off for ranked games.
+    pub undo: bool,
+    /// Only deals boards that can be cleared from the first click without
+    /// guessing. Implies at least a safe first cell.
+    pub no_guess: Option<NoGuess>,
+    /// Makes fields on opposite edges neighbors, as on a torus.
+    pub wrap_around: bool,
+    /// Lets `toggle_flag` turn a flag into a question mark before clearing
+    /// the field again.
+    pub question_marks: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            first_click: FirstClick::default(),
+            chording: Chording::default(),
+            chord_on_open: false,
+            undo: true,
+            no_guess: None,
+            wrap_around: false,
+            question_marks: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Minesweeper {
-    width: usize,
-    height: usize,
-    open_fields: HashSet<Position>,
-    mines: HashSet<Position>,
-    flagged_fields: HashSet<Position>,
-    lost: bool,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) mine_count: usize,
+    pub(crate) rules: Rules,
+    pub(crate) seed: Option<u64>,
+    pub(crate) rng: Box<dyn MineRng>,
+    pub(crate) open_fields: FieldSet,
+    pub(crate) mines: FieldSet,
+    pub(crate) mines_placed: bool,
+    pub(crate) marks: HashMap<Position, Mark>,
+    pub(crate) status: GameStatus,
+    pub(crate) moves: usize,
+    pub(crate) hints: usize,
+    pub(crate) clicks: Clicks,
+    pub(crate) clock: Clock,
+    pub(crate) move_log: Vec<Move>,
+    pub(crate) redo_log: Vec<Move>,
+    pub(crate) time: Box<dyn TimeSource>,
+    pub(crate) replay_start: Option<u64>,
+    pub(crate) replay_events: Vec<ReplayEvent>,
+    /// Fields that were open or marked before any recorded event.
+    pub(crate) starting_fields: StartingFields,
 }
 
 impl Display for Minesweeper {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for row in 0..self.height {
             for col in 0..self.width {
-                let pos = (col, row);
-
-                if !self.open_fields.contains(&pos) {
-                    if self.lost && self.mines.contains(&pos) {
-                        f.write_str("💣 ")?;
-                    } else if self.flagged_fields.contains(&pos) {
-                        f.write_str("🚩 ")?;
-                    } else {
-                        f.write_str("🟦 ")?;
-                    }
-                } else if self.mines.contains(&pos) {
-                    f.write_str("💣 ")?;
-                } else {
-                    let mine_count = self.neighboring_mines(pos);
-
-                    if mine_count > 0 {
-                        write!(f, " {} ", mine_count)?;
-                    } else {
-                        f.write_str("⬜ ")?;
-                    }
+                match self.cell_state((col, row)) {
+                    CellState::Hidden => f.write_str("🟦 ")?,
+                    CellState::Flagged | CellState::WrongFlag => f.write_str("🚩 ")?,
+                    CellState::Questioned => f.write_str("❓ ")?,
+                    CellState::Mine | CellState::ExplodedMine => f.write_str("💣 ")?,
+                    CellState::Open(0) => f.write_str("⬜ ")?,
+                    CellState::Open(mine_count) => write!(f, " {} ", mine_count)?,
                 }
             }
 
@@ -55,33 +170,214 @@ impl Display for Minesweeper {
 }
 
 impl Minesweeper {
-    pub fn new(width: usize, height: usize, mine_count: usize) -> Minesweeper {
-        Minesweeper {
+    pub fn new(width: usize, height: usize, mine_count: usize) -> Result<Minesweeper, BoardError> {
+        Minesweeper::with_rules(width, height, mine_count, Rules::default())
+    }
+
+    pub fn with_rules(width: usize, height: usize, mine_count: usize, rules: Rules) -> Result<Minesweeper, BoardError> {
+        Minesweeper::with_seed(width, height, mine_count, rules, random_seed())
+    }
+
+    /// Builds a game whose mine layout only depends on the seed, the board
+    /// dimensions, the mine count, the rules and, when mines are placed
+    /// lazily, the first opened position.
+    pub fn with_seed(width: usize, height: usize, mine_count: usize, rules: Rules, seed: u64) -> Result<Minesweeper, BoardError> {
+        Minesweeper::build(width, height, mine_count, rules, Some(seed), Box::new(SeededRng::new(seed)))
+    }
+
+    pub fn with_rng(width: usize, height: usize, mine_count: usize, rules: Rules, rng: impl MineRng + 'static) -> Result<Minesweeper, BoardError> {
+        Minesweeper::build(width, height, mine_count, rules, None, Box::new(rng))
+    }
+
+    /// Builds a game with a fixed mine layout. Since the mines are known up
+    /// front, the first click is never protected.
+    pub fn with_mines(width: usize, height: usize, mines: &[Position], rules: Rules) -> Result<Minesweeper, BoardError> {
+        let rules = Rules { first_click: FirstClick::Unprotected, no_guess: None, ..rules };
+        let mut minesweeper = Minesweeper::build(width, height, 0, rules, None, Box::new(SeededRng::new(random_seed())))?;
+
+        for &(x, y) in mines {
+            if x >= width || y >= height {
+                return Err(BoardError::MineOutsideBoard((x, y)));
+            }
+
+            minesweeper.mines.insert((x, y));
+        }
+
+        minesweeper.mine_count = minesweeper.mines.len();
+
+        Ok(minesweeper)
+    }
+
+    fn build(width: usize, height: usize, mine_count: usize, rules: Rules, seed: Option<u64>, rng: Box<dyn MineRng>) -> Result<Minesweeper, BoardError> {
+        validate(width, height, mine_count, rules)?;
+
+        let mut minesweeper = Minesweeper {
             width,
             height,
-            open_fields: HashSet::new(),
-            mines: {
-                let mut mines = HashSet::new();
+            mine_count,
+            rules,
+            seed,
+            rng,
+            open_fields: FieldSet::new(width, height),
+            mines: FieldSet::new(width, height),
+            mines_placed: false,
+            marks: HashMap::new(),
+            status: GameStatus::NotStarted,
+            moves: 0,
+            hints: 0,
+            clicks: Clicks::default(),
+            clock: Clock::default(),
+            move_log: Vec::new(),
+            redo_log: Vec::new(),
+            time: Box::new(SystemTime),
+            replay_start: None,
+            replay_events: Vec::new(),
+            starting_fields: StartingFields::default(),
+        };
+
+        if rules.first_click == FirstClick::Unprotected && rules.no_guess.is_none() {
+            minesweeper.place_mines(&[]);
+        }
+
+        Ok(minesweeper)
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn mine_count(&self) -> usize {
+        self.mine_count
+    }
+
+    pub fn status(&self) -> GameStatus {
+        self.status
+    }
+
+    /// Replaces the clock used to time the game and timestamp recorded
+    /// moves.
+    pub fn set_time_source(&mut self, time: impl TimeSource + 'static) {
+        self.time = Box::new(time);
+    }
+
+    /// Number of successful opens, chords and flag toggles so far.
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Seed the mine layout was generated from, or `None` when the game was
+    /// built from a caller-provided RNG.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
 
-                while mines.len() < mine_count {
-                    mines.insert((random_range(0, width), random_range(0, height)));
+    /// Draws `mine_count` distinct cells outside of `safe` with a partial
+    /// Fisher-Yates shuffle, so the cost is linear in the board size no
+    /// matter how dense the board is.
+    pub(crate) fn place_mines(&mut self, safe: &[Position]) {
+        let width = self.width;
+        let mut candidates: Vec<Position> = (0..width * self.height)
+            .map(|index| (index % width, index / width))
+            .filter(|pos| !safe.contains(pos))
+            .collect();
+
+        for i in 0..self.mine_count {
+            let j = i + self.rng.below(candidates.len() - i);
+            candidates.swap(i, j);
+            self.mines.insert(candidates[i]);
+        }
+
+        self.mines_placed = true;
+    }
+
+    fn safe_zone(&self, position: Position) -> Vec<Position> {
+        let cell_count = self.width * self.height;
+
+        match self.rules.first_click {
+            FirstClick::Unprotected if self.rules.no_guess.is_none() => vec![],
+            FirstClick::Unprotected | FirstClick::SafeCell => vec![position],
+            FirstClick::SafeNeighborhood => {
+                let zone: Vec<Position> = self.iter_neighbors(position)
+                    .chain(std::iter::once(position))
+                    .collect();
+
+                if cell_count - zone.len() >= self.mine_count {
+                    zone
+                } else {
+                    vec![position]
                 }
+            }
+        }
+    }
+
+    pub fn cell(&self, pos: Position) -> Option<CellState> {
+        let (x, y) = pos;
+
+        if x < self.width && y < self.height {
+            Some(self.cell_state(pos))
+        } else {
+            None
+        }
+    }
 
-                mines
-            },
-            flagged_fields: HashSet::new(),
-            lost: false,
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            width: self.width,
+            height: self.height,
+            status: self.status,
+            elapsed_ms: self.elapsed_ms(),
+            wrap_around: self.rules.wrap_around,
+            cells: (0..self.width * self.height)
+                .map(|index| self.cell_state((index % self.width, index / self.width)))
+                .collect(),
         }
     }
 
-    fn iter_neighbors(&self, (x, y): Position) -> impl Iterator<Item=Position> {
-        let width = self.width;
-        let height = self.height;
+    fn cell_state(&self, pos: Position) -> CellState {
+        let lost = self.status == GameStatus::Lost;
+        let mine = self.mines.contains(&pos);
+
+        if self.open_fields.contains(&pos) {
+            if mine {
+                CellState::ExplodedMine
+            } else {
+                CellState::Open(self.neighboring_mines(pos))
+            }
+        } else if self.is_flagged(pos) {
+            if lost && !mine {
+                CellState::WrongFlag
+            } else {
+                CellState::Flagged
+            }
+        } else if lost && mine {
+            CellState::Mine
+        } else if self.marks.contains_key(&pos) {
+            CellState::Questioned
+        } else {
+            CellState::Hidden
+        }
+    }
 
-        (x.max(1) - 1..=(x + 1).min(width - 1))
-            .flat_map(move |i| (y.max(1) - 1..=(y + 1).min(height - 1))
-                .map(move |j| (i, j)))
-            .filter(move |&pos| pos != (x, y))
+    /// Mark on a hidden field, always `None` once the field is open. Undoing
+    /// the open brings a question mark back.
+    pub fn mark(&self, pos: Position) -> Option<Mark> {
+        self.marks.get(&pos).copied().filter(|_| !self.open_fields.contains(&pos))
+    }
+
+    pub(crate) fn is_flagged(&self, pos: Position) -> bool {
+        self.marks.get(&pos) == Some(&Mark::Flag)
+    }
+
+    pub(crate) fn is_cleared(&self) -> bool {
+        self.open_fields.len() == self.width * self.height - self.mines.len()
+    }
+
+    fn iter_neighbors(&self, pos: Position) -> impl Iterator<Item=Position> {
+        neighbors(self.width, self.height, self.rules.wrap_around, pos)
     }
 
     fn neighboring_mines(&self, pos: Position) -> u8 {
@@ -90,70 +386,292 @@ impl Minesweeper {
             .count() as u8
     }
 
-    pub fn open(&mut self, position: Position) -> Option<OpenResult> {
+    pub fn open(&mut self, position: Position) -> Result<Reveal, MoveError> {
+        self.check_move(position)?;
+
+        let mut opened = Vec::new();
+
         if self.open_fields.contains(&position) {
-            let mine_count = self.neighboring_mines(position);
-
-            let flag_count =
-                self.iter_neighbors(position)
-                    .filter(|neighbor|
-                        self.flagged_fields
-                            .contains(neighbor)
-                    )
-                    .count();
-
-            if mine_count == flag_count as u8 {
-                for neighbor in self.iter_neighbors(position) {
-                    if !self.flagged_fields.contains(&neighbor) && !self.open_fields.contains(&neighbor) {
-                        self.open(neighbor);
-                    }
-                }
+            if !self.rules.chord_on_open {
+                self.clicks.left += 1;
+                self.clicks.wasted += 1;
+                return Err(MoveError::AlreadyOpen);
+            }
+
+            return match self.chord(position)? {
+                Chord::Revealed(reveal) => Ok(reveal),
+                Chord::FlagMismatch { .. } => Ok(self.report(opened)),
+            };
+        }
+
+        self.clicks.left += 1;
+
+        if self.is_flagged(position) {
+            self.clicks.wasted += 1;
+            return Err(MoveError::Flagged);
+        }
+
+        let status_before = self.status;
+
+        if !self.mines_placed {
+            let safe = self.safe_zone(position);
+            self.generate(position, &safe);
+        }
+
+        self.reveal(position, &mut opened);
+
+        let reveal = self.report(opened);
+        self.record_reveal(Action::Open(position), status_before, &reveal);
+
+        Ok(reveal)
+    }
+
+    /// Opens every hidden, unflagged neighbor of an open field once enough
+    /// of its neighbors are flagged, as allowed by `Rules::chording`.
+    pub fn chord(&mut self, position: Position) -> Result<Chord, MoveError> {
+        self.check_move(position)?;
+
+        if self.rules.chording == Chording::Disabled {
+            return Err(MoveError::ChordDisabled);
+        }
+
+        self.clicks.chord += 1;
+
+        if !self.open_fields.contains(&position) {
+            self.clicks.wasted += 1;
+            return Err(MoveError::NotOpen);
+        }
+
+        let mines = self.neighboring_mines(position);
+
+        let flags =
+            self.iter_neighbors(position)
+                .filter(|&neighbor|
+                    self.is_flagged(neighbor)
+                )
+                .count() as u8;
+
+        let allowed = match self.rules.chording {
+            Chording::Disabled => false,
+            Chording::Exact => flags == mines,
+            Chording::AtLeast => flags >= mines,
+        };
+
+        if !allowed {
+            self.clicks.wasted += 1;
+            return Ok(Chord::FlagMismatch { flags, mines });
+        }
+
+        let status_before = self.status;
+        let mut opened = Vec::new();
+        let neighbors: Vec<Position> = self.iter_neighbors(position).collect();
+
+        for neighbor in neighbors {
+            if self.status.is_over() {
+                break;
             }
 
-            return None;
+            if !self.is_flagged(neighbor) && !self.open_fields.contains(&neighbor) {
+                self.reveal(neighbor, &mut opened);
+            }
         }
 
-        if self.lost || self.flagged_fields.contains(&position) { return None; }
+        if opened.is_empty() {
+            self.clicks.wasted += 1;
+        }
 
+        let reveal = self.report(opened);
+        self.record_reveal(Action::Chord(position), status_before, &reveal);
+
+        Ok(Chord::Revealed(reveal))
+    }
+
+    fn record_reveal(&mut self, action: Action, status_before: GameStatus, reveal: &Reveal) {
+        self.record(Move {
+            action,
+            opened: reveal.opened.iter().map(|&(pos, _)| pos).collect(),
+            status_before,
+            status_after: reveal.status,
+        });
+    }
+
+    fn report(&self, opened: Vec<(Position, OpenResult)>) -> Reveal {
+        Reveal {
+            hit_mine: opened.iter().any(|(_, result)| *result == OpenResult::Mine),
+            opened,
+            status: self.status,
+        }
+    }
+
+    /// Opens a hidden, unflagged field and flood fills from it when it has no
+    /// neighboring mines, appending every opened field to `opened`. Uses an
+    /// explicit stack instead of recursion so huge empty areas cannot
+    /// overflow the call stack.
+    fn reveal(&mut self, position: Position, opened: &mut Vec<(Position, OpenResult)>) {
         self.open_fields.insert(position);
+        self.status = GameStatus::Playing;
+
+        if self.mines.contains(&position) {
+            self.status = GameStatus::Lost;
+            opened.push((position, OpenResult::Mine));
+            return;
+        }
 
-        let is_mine = self.mines.contains(&position);
+        let mine_count = self.neighboring_mines(position);
+        opened.push((position, OpenResult::NoMine(mine_count)));
 
-        if is_mine {
-            self.lost = true;
-            Some(OpenResult::Mine)
-        } else {
-            let mine_count = self.neighboring_mines(position);
+        if mine_count == 0 {
+            let mut pending = vec![position];
+
+            while let Some(pos) = pending.pop() {
+                for neighbor in self.iter_neighbors(pos) {
+                    if self.open_fields.contains(&neighbor) || self.is_flagged(neighbor) {
+                        continue;
+                    }
 
-            if mine_count == 0 {
-                for neighbor in self.iter_neighbors(position) {
-                    if !self.open_fields.contains(&neighbor) {
-                        self.open(neighbor);
+                    self.open_fields.insert(neighbor);
+
+                    let neighbor_count = self.neighboring_mines(neighbor);
+                    opened.push((neighbor, OpenResult::NoMine(neighbor_count)));
+
+                    if neighbor_count == 0 {
+                        pending.push(neighbor);
                     }
                 }
             }
-            Some(OpenResult::NoMine(mine_count))
+        }
+
+        if self.is_cleared() {
+            self.status = GameStatus::Won;
         }
     }
 
-    pub fn toggle_flag(&mut self, pos: Position) {
-        if self.lost || self.open_fields.contains(&pos) {
-            return;
+    /// Cycles a hidden cell from unmarked to flagged, then to question-marked
+    /// when `Rules::question_marks` allows it, and back. Returns whether the
+    /// cell is now flagged.
+    pub fn toggle_flag(&mut self, pos: Position) -> Result<bool, MoveError> {
+        self.check_move(pos)?;
+
+        self.clicks.right += 1;
+
+        if self.open_fields.contains(&pos) {
+            self.clicks.wasted += 1;
+            return Err(MoveError::AlreadyOpen);
         }
 
-        if self.flagged_fields.contains(&pos) {
-            self.flagged_fields.remove(&pos);
+        // Taking a flag back wastes both the click that placed it and this one.
+        match self.mark(pos) {
+            Some(Mark::Flag) => self.clicks.wasted += 2,
+            Some(Mark::Question) => self.clicks.wasted += 1,
+            None => {}
+        }
+
+        let status_before = self.status;
+        self.status = GameStatus::Playing;
+
+        self.record(Move {
+            action: Action::ToggleFlag(pos),
+            opened: vec![],
+            status_before,
+            status_after: self.status,
+        });
+
+        Ok(self.cycle_mark(pos, false) == Some(Mark::Flag))
+    }
+
+    /// Moves the mark of `pos` one step through the `toggle_flag` cycle, or
+    /// one step back for undo, and returns the new mark.
+    pub(crate) fn cycle_mark(&mut self, pos: Position, backwards: bool) -> Option<Mark> {
+        let current = self.mark(pos);
+        let question_marks = self.rules.question_marks;
+
+        let next = if backwards {
+            match current {
+                None if question_marks => Some(Mark::Question),
+                None | Some(Mark::Question) => Some(Mark::Flag),
+                Some(Mark::Flag) => None,
+            }
         } else {
-            self.flagged_fields.insert(pos);
+            match current {
+                None => Some(Mark::Flag),
+                Some(Mark::Flag) if question_marks => Some(Mark::Question),
+                Some(_) => None,
+            }
+        };
+
+        match next {
+            Some(mark) => self.marks.insert(pos, mark),
+            None => self.marks.remove(&pos),
+        };
+
+        next
+    }
+
+    fn check_move(&self, (x, y): Position) -> Result<(), MoveError> {
+        if x >= self.width || y >= self.height {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        if self.status.is_over() {
+            return Err(MoveError::GameOver);
         }
+
+        Ok(())
+    }
+}
+
+/// Fields around `pos` in row-major order, including the ones across the
+/// edges when the board wraps around.
+pub(crate) fn neighbors(width: usize, height: usize, wrap_around: bool, (x, y): Position) -> impl Iterator<Item=Position> {
+    let columns = axis_neighbors(x, width, wrap_around);
+    let rows = axis_neighbors(y, height, wrap_around);
+
+    rows.into_iter()
+        .flatten()
+        .flat_map(move |j| columns.into_iter().flatten().map(move |i| (i, j)))
+        .filter(move |&pos| pos != (x, y))
+}
+
+/// The coordinates before, at and after `at` on an axis of length `len`,
+/// each at most once so tiny wrapped boards do not count a field twice.
+fn axis_neighbors(at: usize, len: usize, wrap_around: bool) -> [Option<usize>; 3] {
+    if wrap_around {
+        let before = (at + len - 1) % len;
+        let after = (at + 1) % len;
+
+        [Some(before).filter(|&before| before != at), Some(at), Some(after).filter(|&after| after != at && after != before)]
+    } else {
+        [at.checked_sub(1), Some(at), Some(at + 1).filter(|&after| after < len)]
+    }
+}
+
+pub(crate) fn validate(width: usize, height: usize, mine_count: usize, rules: Rules) -> Result<(), BoardError> {
+    if width == 0 || height == 0 {
+        return Err(BoardError::ZeroDimension);
+    }
+
+    let cell_count = width.checked_mul(height)
+        .filter(|&cell_count| cell_count <= MAX_CELLS)
+        .ok_or(BoardError::TooManyCells { width, height })?;
+
+    if mine_count > cell_count {
+        return Err(BoardError::TooManyMines { mine_count, cell_count });
+    }
+
+    if (rules.first_click != FirstClick::Unprotected || rules.no_guess.is_some()) && mine_count == cell_count {
+        return Err(BoardError::TooManyMinesForSafeStart { mine_count, cell_count });
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        minesweeper::{Minesweeper, Position},
-        random::random_range,
+        error::{BoardError, MoveError},
+        minesweeper::{Chord, Chording, FirstClick, GameStatus, Mark, Minesweeper, OpenResult, Position, Rules, MAX_CELLS},
+        random::{random_range, MineRng},
+        snapshot::CellState,
     };
 
     #[test]
@@ -161,7 +679,7 @@ mod tests {
         let width = random_range(1, 10);
         let height = random_range(1, 10);
         let mine_count = random_range(1, width * height);
-        let minesweeper = Minesweeper::new(width, height, mine_count);
+        let minesweeper = Minesweeper::new(width, height, mine_count).unwrap();
 
         assert_eq!(minesweeper.width, width);
         assert_eq!(minesweeper.height, height);
@@ -170,29 +688,29 @@ mod tests {
 
     #[test]
     fn check_mine_number() {
-        let width = random_range(1, 20);
+        let width = random_range(2, 20);
         let height = random_range(1, 20);
         let mine_count: usize = random_range(0, width / 2);
 
-        let ms = Minesweeper::new(width, height, mine_count);
+        let ms = Minesweeper::new(width, height, mine_count).unwrap();
 
         assert_eq!(ms.mines.len(), mine_count);
     }
 
     #[test]
     fn check_open() {
-        let width = random_range(1, 20);
+        let width = random_range(2, 20);
         let height = random_range(1, 20);
         let mine_count: usize = random_range(0, width / 2);
 
         let opened_position: Position = (random_range(0, width), random_range(0, height));
 
-        let mut ms = Minesweeper::new(width, height, mine_count);
+        let mut ms = Minesweeper::new(width, height, mine_count).unwrap();
 
-        ms.open(opened_position);
+        ms.open(opened_position).unwrap();
 
         if ms.mines.contains(&opened_position) {
-            assert!(ms.lost);
+            assert_eq!(ms.status(), GameStatus::Lost);
         } else {
             assert!(ms.open_fields.contains(&opened_position));
         }
@@ -206,11 +724,349 @@ mod tests {
 
         let flag_pos: Position = (random_range(0, width), random_range(0, height));
 
-        let mut ms = Minesweeper::new(width, height, mine_count);
+        let mut ms = Minesweeper::new(width, height, mine_count).unwrap();
+
+        ms.toggle_flag(flag_pos).unwrap();
+
+        assert_eq!(ms.mark(flag_pos), Some(Mark::Flag));
+    }
+
+    #[test]
+    fn check_win() {
+        let width = random_range(1, 20);
+        let height = random_range(1, 20);
+
+        let mut ms = Minesweeper::new(width, height, 0).unwrap();
+
+        assert_eq!(ms.status(), GameStatus::NotStarted);
+
+        ms.open((random_range(0, width), random_range(0, height))).unwrap();
+
+        assert_eq!(ms.status(), GameStatus::Won);
+    }
+
+    #[test]
+    fn check_no_action_after_loss() {
+        let mut ms = Minesweeper::new(3, 3, 9).unwrap();
+
+        ms.open((1, 1)).unwrap();
+        assert_eq!(ms.status(), GameStatus::Lost);
+
+        assert_eq!(ms.toggle_flag((0, 0)), Err(MoveError::GameOver));
+        assert!(ms.marks.is_empty());
+    }
+
+    #[test]
+    fn check_first_click_safe_cell() {
+        let rules = Rules { first_click: FirstClick::SafeCell, ..Rules::default() };
+        let mut ms = Minesweeper::with_rules(4, 4, 15, rules).unwrap();
+
+        assert_eq!(ms.mines.len(), 0);
+
+        ms.open((2, 1)).unwrap();
+
+        assert_eq!(ms.mines.len(), 15);
+        assert!(!ms.mines.contains(&(2, 1)));
+        assert_eq!(ms.status(), GameStatus::Won);
+    }
+
+    #[test]
+    fn check_first_click_safe_neighborhood() {
+        let width = random_range(3, 20);
+        let height = random_range(3, 20);
+        let mine_count = random_range(0, width * height - 8);
+        let opened_position: Position = (random_range(0, width), random_range(0, height));
+
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+        let mut ms = Minesweeper::with_rules(width, height, mine_count, rules).unwrap();
+
+        ms.open(opened_position).unwrap();
+
+        assert_eq!(ms.mines.len(), mine_count);
+        assert_eq!(ms.neighboring_mines(opened_position), 0);
+        assert!(!ms.mines.contains(&opened_position));
+        assert_ne!(ms.status(), GameStatus::Lost);
+    }
+
+    #[test]
+    fn check_same_seed_same_layout() {
+        let seed = random_range(0, usize::MAX) as u64;
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+
+        let mut first = Minesweeper::with_seed(16, 16, 40, rules, seed).unwrap();
+        let mut second = Minesweeper::with_seed(16, 16, 40, rules, seed).unwrap();
+
+        first.open((3, 7)).unwrap();
+        second.open((3, 7)).unwrap();
+
+        assert_eq!(first.seed(), Some(seed));
+        assert_eq!(first.mines, second.mines);
+        assert_eq!(first.open_fields, second.open_fields);
+    }
+
+    #[derive(Debug)]
+    struct Counter(u64);
+
+    impl MineRng for Counter {
+        fn next_u64(&mut self) -> u64 {
+            self.0 += 1;
+            self.0
+        }
+    }
+
+    #[test]
+    fn check_custom_rng() {
+        let ms = Minesweeper::with_rng(4, 4, 2, Rules::default(), Counter(0)).unwrap();
+
+        assert_eq!(ms.seed(), None);
+        assert_eq!(ms.mines.len(), 2);
+        assert!(ms.mines.contains(&(1, 0)) && ms.mines.contains(&(3, 0)));
+    }
+
+    #[test]
+    fn check_invalid_boards() {
+        assert_eq!(Minesweeper::new(0, 5, 0).unwrap_err(), BoardError::ZeroDimension);
+        assert_eq!(Minesweeper::new(5, 0, 0).unwrap_err(), BoardError::ZeroDimension);
+        assert_eq!(
+            Minesweeper::new(3, 3, 10).unwrap_err(),
+            BoardError::TooManyMines { mine_count: 10, cell_count: 9 }
+        );
+        assert_eq!(
+            Minesweeper::new(usize::MAX / 2 + 1, 2, 0).unwrap_err(),
+            BoardError::TooManyCells { width: usize::MAX / 2 + 1, height: 2 }
+        );
+        assert_eq!(
+            Minesweeper::new(MAX_CELLS + 1, 1, 0).unwrap_err(),
+            BoardError::TooManyCells { width: MAX_CELLS + 1, height: 1 }
+        );
+
+        let rules = Rules { first_click: FirstClick::SafeCell, ..Rules::default() };
 
-        ms.toggle_flag(flag_pos);
+        assert_eq!(
+            Minesweeper::with_rules(3, 3, 9, rules).unwrap_err(),
+            BoardError::TooManyMinesForSafeStart { mine_count: 9, cell_count: 9 }
+        );
+    }
+
+    #[test]
+    fn check_full_density() {
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+        let mut ms = Minesweeper::with_rules(300, 300, 300 * 300 - 1, rules).unwrap();
+
+        ms.open((150, 150)).unwrap();
+
+        assert_eq!(ms.mines.len(), 300 * 300 - 1);
+        assert_eq!(ms.status(), GameStatus::Won);
+    }
+
+    #[test]
+    fn check_move_errors() {
+        let mut ms = Minesweeper::new(5, 5, 0).unwrap();
+
+        assert_eq!(ms.open((5, 0)).err(), Some(MoveError::OutOfBounds));
+        assert_eq!(ms.toggle_flag((0, 5)), Err(MoveError::OutOfBounds));
+        assert_eq!(ms.open_fields.len(), 0);
+        assert!(ms.marks.is_empty());
+
+        assert_eq!(ms.toggle_flag((1, 1)), Ok(true));
+        assert_eq!(ms.open((1, 1)).err(), Some(MoveError::Flagged));
+        assert_eq!(ms.toggle_flag((1, 1)), Ok(false));
+
+        ms.open((1, 1)).unwrap();
+
+        assert_eq!(ms.status(), GameStatus::Won);
+        assert_eq!(ms.open((2, 2)).err(), Some(MoveError::GameOver));
+    }
+
+    #[test]
+    fn check_flag_open_field() {
+        let mut ms = Minesweeper::from_ascii("\
+            * . .\n\
+            . . .\n", Rules::default()).unwrap();
+
+        assert_eq!(ms.open((1, 0)).unwrap().opened, vec![((1, 0), OpenResult::NoMine(1))]);
+        assert_eq!(ms.status(), GameStatus::Playing);
+        assert_eq!(ms.toggle_flag((1, 0)), Err(MoveError::AlreadyOpen));
+    }
+
+    #[test]
+    fn check_large_cascade_without_recursion() {
+        let opened = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(|| {
+                let mut ms = Minesweeper::new(400, 400, 0).unwrap();
+                ms.open((200, 200)).unwrap();
+                (ms.open_fields.len(), ms.status())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(opened, (160_000, GameStatus::Won));
+    }
+
+    #[test]
+    fn check_reveal_lists_cascade() {
+        let mut ms = Minesweeper::with_seed(8, 8, 6, Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() }, 7).unwrap();
+
+        let reveal = ms.open((4, 4)).unwrap();
+
+        assert!(!reveal.hit_mine);
+        assert_eq!(reveal.status, ms.status());
+        assert_eq!(reveal.opened[0], ((4, 4), OpenResult::NoMine(0)));
+        assert_eq!(reveal.opened.len(), ms.open_fields.len());
+
+        for &(pos, result) in &reveal.opened {
+            assert!(ms.open_fields.contains(&pos));
+            assert_eq!(result, OpenResult::NoMine(ms.neighboring_mines(pos)));
+        }
+    }
+
+    #[test]
+    fn check_reveal_reports_mine() {
+        let mut ms = Minesweeper::new(2, 2, 4).unwrap();
+
+        let reveal = ms.open((0, 1)).unwrap();
+
+        assert!(reveal.hit_mine);
+        assert_eq!(reveal.status, GameStatus::Lost);
+        assert_eq!(reveal.opened, [((0, 1), OpenResult::Mine)]);
+    }
+
+    fn chord_board(rules: Rules) -> Minesweeper {
+        let map = "\
+            * . .\n\
+            . o .\n\
+            . . .\n";
+
+        Minesweeper::from_ascii(map, rules).unwrap()
+    }
+
+    #[test]
+    fn check_chord() {
+        let mut ms = chord_board(Rules::default());
 
-        assert_eq!(ms.flagged_fields.is_empty(), false);
-        assert!(ms.flagged_fields.contains(&flag_pos));
+        assert_eq!(ms.chord((0, 1)), Err(MoveError::NotOpen));
+        assert_eq!(ms.chord((1, 1)), Ok(Chord::FlagMismatch { flags: 0, mines: 1 }));
+        assert_eq!(ms.open((1, 1)), Err(MoveError::AlreadyOpen));
+
+        ms.toggle_flag((0, 0)).unwrap();
+
+        match ms.chord((1, 1)).unwrap() {
+            Chord::Revealed(reveal) => {
+                assert_eq!(reveal.opened.len(), 7);
+                assert_eq!(reveal.status, GameStatus::Won);
+            }
+            chord => panic!("unexpected {:?}", chord),
+        }
+    }
+
+    #[test]
+    fn check_chord_rules() {
+        let mut ms = chord_board(Rules { chording: Chording::Disabled, ..Rules::default() });
+        assert_eq!(ms.chord((1, 1)), Err(MoveError::ChordDisabled));
+
+        let mut ms = chord_board(Rules { chording: Chording::AtLeast, ..Rules::default() });
+        ms.toggle_flag((0, 0)).unwrap();
+        ms.toggle_flag((0, 1)).unwrap();
+        assert!(matches!(ms.chord((1, 1)), Ok(Chord::Revealed(_))));
+
+        let mut ms = chord_board(Rules { chord_on_open: true, ..Rules::default() });
+        ms.toggle_flag((0, 1)).unwrap();
+        let reveal = ms.open((1, 1)).unwrap();
+        assert!(reveal.hit_mine);
+        assert_eq!(ms.status(), GameStatus::Lost);
+    }
+
+    #[test]
+    fn check_question_marks() {
+        let mut ms = chord_board(Rules { question_marks: true, ..Rules::default() });
+
+        assert_eq!(ms.toggle_flag((0, 0)), Ok(true));
+        assert_eq!(ms.toggle_flag((0, 0)), Ok(false));
+        assert_eq!(ms.mark((0, 0)), Some(Mark::Question));
+        assert_eq!(ms.cell((0, 0)), Some(CellState::Questioned));
+        assert!(ms.to_string().starts_with("❓ "));
+        assert_eq!(ms.chord((1, 1)), Ok(Chord::FlagMismatch { flags: 0, mines: 1 }));
+
+        assert_eq!(ms.toggle_flag((0, 0)), Ok(false));
+        assert_eq!(ms.mark((0, 0)), None);
+
+        ms.undo().unwrap();
+        assert_eq!(ms.mark((0, 0)), Some(Mark::Question));
+        ms.undo().unwrap();
+        assert_eq!(ms.mark((0, 0)), Some(Mark::Flag));
+        ms.redo().unwrap();
+        assert_eq!(ms.mark((0, 0)), Some(Mark::Question));
+
+        ms.toggle_flag((2, 2)).unwrap();
+        ms.toggle_flag((2, 2)).unwrap();
+        assert_eq!(ms.open((2, 2)).unwrap().status, GameStatus::Won);
+        assert_eq!(ms.cell((2, 2)), Some(CellState::Open(0)));
+        assert_eq!(ms.mark((2, 2)), None);
+
+        ms.undo().unwrap();
+        assert_eq!(ms.mark((2, 2)), Some(Mark::Question));
+    }
+
+    #[test]
+    fn check_snapshot() {
+        let mut ms = chord_board(Rules::default());
+
+        assert_eq!(ms.cell((3, 0)), None);
+        assert_eq!(ms.cell((1, 1)), Some(CellState::Open(1)));
+        assert_eq!(ms.cell((0, 0)), Some(CellState::Hidden));
+
+        ms.toggle_flag((0, 1)).unwrap();
+        ms.open((0, 0)).unwrap();
+
+        let snapshot = ms.snapshot();
+
+        assert_eq!(snapshot.status, GameStatus::Lost);
+        assert_eq!(snapshot.get((0, 0)), Some(CellState::ExplodedMine));
+        assert_eq!(snapshot.get((0, 1)), Some(CellState::WrongFlag));
+        assert_eq!(snapshot.get((2, 2)), Some(CellState::Hidden));
+        assert_eq!(snapshot.codes()[4], 1);
+
+        for code in snapshot.codes() {
+            assert_eq!(CellState::from_code(code).map(CellState::code), Some(code));
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn check_cascade_stops_at_numbers() {
+        let mut ms = Minesweeper::from_ascii("\
+            . . . . .\n\
+            . . . . .\n\
+            * * * . .\n\
+            . . . . *\n", Rules::default()).unwrap();
+
+        let reveal = ms.open((0, 0)).unwrap();
+
+        assert_eq!(reveal.opened.len(), 12);
+        assert_eq!(ms.to_ascii(), "\
+            ooooo\n\
+            ooooo\n\
+            ***oo\n\
+            ....*\n".replace(' ', ""));
+        assert_eq!(ms.cell((3, 2)), Some(CellState::Open(2)));
+        assert_eq!(ms.cell((4, 2)), Some(CellState::Open(1)));
+        assert_eq!(ms.cell((3, 3)), Some(CellState::Hidden));
+        assert_eq!(ms.status(), GameStatus::Playing);
+    }
+
+    #[test]
+    fn check_chord_cascades_through_empty_neighbors() {
+        let mut ms = Minesweeper::from_ascii("\
+            F o . . .\n\
+            o o . . .\n\
+            . . . . .\n", Rules::default()).unwrap();
+
+        match ms.chord((1, 1)).unwrap() {
+            Chord::Revealed(reveal) => assert_eq!(reveal.status, GameStatus::Won),
+            chord => panic!("unexpected {:?}", chord),
+        }
+
+        assert_eq!(ms.to_ascii(), "Foooo\nooooo\nooooo\n");
+    }
+}
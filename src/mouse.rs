@@ -0,0 +1,205 @@
+use crate::error::FormatError;
+use crate::history::Action;
+use crate::minesweeper::{Chord, Minesweeper, Position, Rules};
+use crate::replay::{Replay, ReplayAction, ReplayEvent, StartingFields, REPLAY_VERSION};
+
+/// Size of a field in the pixel coordinates used by community replay files.
+pub const CELL_PIXELS: u16 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseKind {
+    Move,
+    LeftDown,
+    LeftUp,
+    RightDown,
+    RightUp,
+    MiddleDown,
+    MiddleUp,
+}
+
+/// Raw mouse input as stored by Arbiter and Viennasweeper replays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub kind: MouseKind,
+    pub time_ms: u64,
+    pub x: u16,
+    pub y: u16,
+}
+
+impl MouseEvent {
+    fn field(&self) -> Position {
+        ((self.x / CELL_PIXELS) as usize, (self.y / CELL_PIXELS) as usize)
+    }
+}
+
+/// Turns mouse input into board actions the way classic clients do: a left
+/// release opens, a right press flags, and releasing either button while the
+/// other one is held (or releasing the middle button) chords. Clicks that do
+/// not change the board are dropped. `preflags` are flagged before the
+/// first event.
+pub(crate) fn to_replay(width: usize, height: usize, mines: Vec<Position>, preflags: &[Position], rules: Rules, events: &[MouseEvent]) -> Result<Replay, FormatError> {
+    if preflags.iter().any(|&(x, y)| x >= width || y >= height) {
+        return Err(FormatError::Invalid("preflag outside the board"));
+    }
+
+    let mut game = Minesweeper::with_mines(width, height, &mines, rules)?;
+    game.preset_fields(&[], preflags, &[]);
+    let mut replay_events = Vec::new();
+    let (mut left, mut right, mut chorded) = (false, false, false);
+
+    for event in events {
+        let action = match event.kind {
+            MouseKind::Move => None,
+            MouseKind::LeftDown => {
+                left = true;
+                None
+            }
+            MouseKind::RightDown => {
+                right = true;
+                (!left).then(|| Action::ToggleFlag(event.field()))
+            }
+            MouseKind::LeftUp => {
+                left = false;
+                let action = if right {
+                    chorded = true;
+                    Some(Action::Chord(event.field()))
+                } else {
+                    (!chorded).then(|| Action::Open(event.field()))
+                };
+                chorded &= right;
+                action
+            }
+            MouseKind::RightUp => {
+                right = false;
+                let action = if left {
+                    chorded = true;
+                    Some(Action::Chord(event.field()))
+                } else {
+                    None
+                };
+                chorded &= left;
+                action
+            }
+            MouseKind::MiddleDown => None,
+            MouseKind::MiddleUp => Some(Action::Chord(event.field())),
+        };
+
+        let Some(action) = action else { continue };
+        let (x, y) = action_field(action);
+
+        if x >= width || y >= height || game.status().is_over() {
+            continue;
+        }
+
+        let changed = match action {
+            Action::Open(pos) => game.open(pos).is_ok(),
+            Action::Chord(pos) => matches!(game.chord(pos), Ok(Chord::Revealed(_))),
+            Action::ToggleFlag(pos) => game.toggle_flag(pos).is_ok(),
+        };
+
+        if changed {
+            replay_events.push(ReplayEvent { time_ms: event.time_ms, action: ReplayAction::Play(action) });
+        }
+    }
+
+    Ok(Replay {
+        version: REPLAY_VERSION,
+        width,
+        height,
+        rules: game.rules(),
+        seed: None,
+        mines,
+        starting_fields: game.starting_fields.clone(),
+        events: replay_events,
+        status: game.status(),
+        open_fields: game.open_fields.len(),
+    })
+}
+
+/// Mouse input that reproduces every action of a replay. Undo and redo have
+/// no mouse equivalent, so replays using them cannot be converted. Starting
+/// flags are left to the caller.
+pub(crate) fn from_replay(replay: &Replay) -> Result<Vec<MouseEvent>, FormatError> {
+    let StartingFields { open, questioned, .. } = &replay.starting_fields;
+
+    if !open.is_empty() || !questioned.is_empty() {
+        return Err(FormatError::Unsupported("games that did not start on a blank board"));
+    }
+
+    let mut events = Vec::new();
+
+    for event in &replay.events {
+        let ReplayAction::Play(action) = event.action else {
+            return Err(FormatError::Unsupported("undo and redo"));
+        };
+
+        let (x, y) = action_field(action);
+        let center = |field: usize| field as u16 * CELL_PIXELS + CELL_PIXELS / 2;
+        let press = |kind| MouseEvent { kind, time_ms: event.time_ms, x: center(x), y: center(y) };
+
+        match action {
+            Action::Open(_) => events.extend([press(MouseKind::LeftDown), press(MouseKind::LeftUp)]),
+            Action::ToggleFlag(_) => events.extend([press(MouseKind::RightDown), press(MouseKind::RightUp)]),
+            Action::Chord(_) => events.extend([press(MouseKind::MiddleDown), press(MouseKind::MiddleUp)]),
+        }
+    }
+
+    Ok(events)
+}
+
+fn action_field(action: Action) -> Position {
+    match action {
+        Action::Open(pos) | Action::Chord(pos) | Action::ToggleFlag(pos) => pos,
+    }
+}
+
+/// Cursor over a byte slice for the binary replay formats.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, offset: 0 }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        let bytes = self.bytes.get(self.offset..self.offset + len).ok_or(FormatError::UnexpectedEof)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, FormatError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, FormatError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn u24(&mut self) -> Result<u32, FormatError> {
+        let bytes = self.take(3)?;
+        Ok(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, FormatError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Bytes up to the next occurrence of `byte`, which is skipped too.
+    pub(crate) fn take_until(&mut self, byte: u8) -> Result<&'a [u8], FormatError> {
+        let len = self.bytes[self.offset.min(self.bytes.len())..].iter()
+            .position(|&next| next == byte)
+            .ok_or(FormatError::UnexpectedEof)?;
+        let bytes = self.take(len)?;
+        self.offset += 1;
+        Ok(bytes)
+    }
+}
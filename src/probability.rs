@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use crate::minesweeper::{Minesweeper, Position};
+use crate::snapshot::Snapshot;
+use crate::solver::{constraints, known_fields, Constraint};
+
+/// Fields next to open numbers that are connected through shared numbers,
+/// so their mines have to be counted together.
+#[derive(Debug, Default)]
+struct Component {
+    fields: Vec<Position>,
+    /// Indices into `fields` together with the mines among them.
+    constraints: Vec<(Vec<usize>, usize)>,
+}
+
+/// Solutions of a component by the number of mines they contain.
+#[derive(Debug)]
+struct Tally {
+    solutions: Vec<f64>,
+    /// How many of those solutions have a mine on each field.
+    field_mines: Vec<Vec<f64>>,
+}
+
+struct Search<'a> {
+    component: &'a Component,
+    by_field: Vec<Vec<usize>>,
+    placed: Vec<usize>,
+    unassigned: Vec<usize>,
+    mines: Vec<bool>,
+    tally: Tally,
+}
+
+/// Chance of a mine under every field, row by row, given what the player can
+/// see and the total number of mines.
+///
+/// Every arrangement of mines that agrees with the open numbers is counted,
+/// fields away from any number included, so the result is exact. The work
+/// grows exponentially with the longest stretch of connected numbers. Open
+/// fields are `0` and flags `1`; if the flags contradict the numbers, hidden
+/// fields get the plain density of the remaining mines.
+pub fn mine_probabilities(snapshot: &Snapshot, mine_count: usize) -> Vec<f64> {
+    let known = known_fields(snapshot);
+    let mut probabilities: Vec<f64> = known.iter().map(|field| if *field == Some(true) { 1.0 } else { 0.0 }).collect();
+
+    let unknown = known.iter().filter(|field| field.is_none()).count();
+    let known_mines = known.iter().filter(|&&field| field == Some(true)).count();
+
+    if unknown == 0 {
+        return probabilities;
+    }
+
+    let components = components(&constraints(snapshot, &known));
+    let frontier: usize = components.iter().map(|component| component.fields.len()).sum();
+    let interior = unknown - frontier;
+    let tallies: Vec<Tally> = components.iter().map(enumerate).collect();
+
+    let weights = mine_count.checked_sub(known_mines)
+        .map(|remaining| interior_weights(remaining, interior, frontier))
+        .unwrap_or_default();
+
+    // Solutions of all components but one, by their number of mines.
+    let mut prefix = vec![vec![1.0]];
+    for tally in &tallies {
+        prefix.push(convolve(prefix.last().unwrap(), &tally.solutions));
+    }
+
+    let mut suffix = vec![vec![1.0]];
+    for tally in tallies.iter().rev() {
+        suffix.push(convolve(suffix.last().unwrap(), &tally.solutions));
+    }
+    suffix.reverse();
+
+    let total = &prefix[tallies.len()];
+    let weight = |mines: usize| weights.get(mines).copied().unwrap_or(0.0);
+    let sum: f64 = total.iter().enumerate().map(|(mines, count)| count * weight(mines)).sum();
+
+    if sum == 0.0 {
+        let remaining = mine_count.saturating_sub(known_mines);
+        let density = (remaining as f64 / unknown as f64).min(1.0);
+
+        for (probability, field) in probabilities.iter_mut().zip(&known) {
+            if field.is_none() {
+                *probability = density;
+            }
+        }
+
+        return probabilities;
+    }
+
+    for (index, (component, tally)) in components.iter().zip(&tallies).enumerate() {
+        let rest = convolve(&prefix[index], &suffix[index + 1]);
+
+        for (field, &(x, y)) in component.fields.iter().enumerate() {
+            let mut mines = 0.0;
+
+            for (own, field_mines) in tally.field_mines.iter().enumerate() {
+                for (other, count) in rest.iter().enumerate() {
+                    mines += field_mines[field] * count * weight(own + other);
+                }
+            }
+
+            probabilities[y * snapshot.width + x] = mines / sum;
+        }
+    }
+
+    if interior > 0 {
+        let remaining = mine_count - known_mines;
+        let interior_mines: f64 = total.iter()
+            .enumerate()
+            .map(|(mines, count)| count * weight(mines) * remaining.saturating_sub(mines) as f64)
+            .sum();
+        let interior_probability = interior_mines / sum / interior as f64;
+
+        let mut on_frontier = vec![false; known.len()];
+
+        for &(x, y) in components.iter().flat_map(|component| &component.fields) {
+            on_frontier[y * snapshot.width + x] = true;
+        }
+
+        for (index, field) in known.iter().enumerate() {
+            if field.is_none() && !on_frontier[index] {
+                probabilities[index] = interior_probability;
+            }
+        }
+    }
+
+    probabilities
+}
+
+impl Minesweeper {
+    /// See `probability::mine_probabilities`.
+    pub fn mine_probabilities(&self) -> Vec<f64> {
+        mine_probabilities(&self.snapshot(), self.mine_count)
+    }
+}
+
+fn components(constraints: &[Constraint]) -> Vec<Component> {
+    let mut fields: HashMap<Position, usize> = HashMap::new();
+    let mut parents: Vec<usize> = Vec::new();
+
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+
+        index
+    }
+
+    for constraint in constraints {
+        let mut first = None;
+
+        for field in &constraint.fields {
+            let index = *fields.entry(*field).or_insert_with(|| {
+                parents.push(parents.len());
+                parents.len() - 1
+            });
+
+            match first {
+                None => first = Some(index),
+                Some(first) => {
+                    let (a, b) = (root(&mut parents, first), root(&mut parents, index));
+                    parents[a] = b;
+                }
+            }
+        }
+    }
+
+    let mut components: Vec<Component> = Vec::new();
+    let mut by_root: HashMap<usize, usize> = HashMap::new();
+
+    for constraint in constraints {
+        let root = root(&mut parents, fields[&constraint.fields[0]]);
+        let index = *by_root.entry(root).or_insert_with(|| {
+            components.push(Component::default());
+            components.len() - 1
+        });
+
+        let component = &mut components[index];
+        let indices = constraint.fields.iter()
+            .map(|field| match component.fields.iter().position(|other| other == field) {
+                Some(index) => index,
+                None => {
+                    component.fields.push(*field);
+                    component.fields.len() - 1
+                }
+            })
+            .collect();
+
+        component.constraints.push((indices, constraint.mines));
+    }
+
+    components
+}
+
+fn enumerate(component: &Component) -> Tally {
+    let size = component.fields.len();
+    let mut by_field = vec![Vec::new(); size];
+
+    for (index, (fields, _)) in component.constraints.iter().enumerate() {
+        for &field in fields {
+            by_field[field].push(index);
+        }
+    }
+
+    let mut search = Search {
+        component,
+        by_field,
+        placed: vec![0; component.constraints.len()],
+        unassigned: component.constraints.iter().map(|(fields, _)| fields.len()).collect(),
+        mines: vec![false; size],
+        tally: Tally {
+            solutions: vec![0.0; size + 1],
+            field_mines: vec![vec![0.0; size]; size + 1],
+        },
+    };
+
+    search.search(0, 0);
+    search.tally
+}
+
+impl Search<'_> {
+    fn search(&mut self, field: usize, mines: usize) {
+        if field == self.mines.len() {
+            self.tally.solutions[mines] += 1.0;
+
+            for (index, &mine) in self.mines.iter().enumerate() {
+                if mine {
+                    self.tally.field_mines[mines][index] += 1.0;
+                }
+            }
+
+            return;
+        }
+
+        for mine in [false, true] {
+            self.mines[field] = mine;
+
+            for &constraint in &self.by_field[field] {
+                self.unassigned[constraint] -= 1;
+                self.placed[constraint] += mine as usize;
+            }
+
+            let possible = self.by_field[field].iter().all(|&constraint| {
+                let needed = self.component.constraints[constraint].1;
+                self.placed[constraint] <= needed && self.placed[constraint] + self.unassigned[constraint] >= needed
+            });
+
+            if possible {
+                self.search(field + 1, mines + mine as usize);
+            }
+
+            for &constraint in &self.by_field[field] {
+                self.unassigned[constraint] += 1;
+                self.placed[constraint] -= mine as usize;
+            }
+        }
+
+        self.mines[field] = false;
+    }
+}
+
+/// Relative number of ways to put the mines left over by a frontier
+/// solution on the fields away from any number, by frontier mine count.
+/// Kept as ratios to the largest so big boards do not overflow.
+fn interior_weights(remaining: usize, interior: usize, frontier: usize) -> Vec<f64> {
+    let mut ln_factorial = vec![0.0; interior + 1];
+
+    for n in 1..=interior {
+        ln_factorial[n] = ln_factorial[n - 1] + (n as f64).ln();
+    }
+
+    let ln_weights: Vec<Option<f64>> = (0..=frontier)
+        .map(|mines| {
+            let left = remaining.checked_sub(mines).filter(|&left| left <= interior)?;
+            Some(ln_factorial[interior] - ln_factorial[left] - ln_factorial[interior - left])
+        })
+        .collect();
+
+    let max = ln_weights.iter().flatten().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    ln_weights.into_iter()
+        .map(|weight| weight.map_or(0.0, |weight| (weight - max).exp()))
+        .collect()
+}
+
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut result = vec![0.0; a.len() + b.len() - 1];
+
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            result[i + j] += x * y;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        minesweeper::{FirstClick, Minesweeper, Rules},
+        snapshot::CellState,
+    };
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn check_even_split() {
+        let ms = Minesweeper::from_ascii("\
+            o o\n\
+            * .\n", Rules::default()).unwrap();
+
+        let probabilities = ms.mine_probabilities();
+
+        assert_eq!(&probabilities[..2], &[0.0, 0.0]);
+        assert_close(probabilities[2], 0.5);
+        assert_close(probabilities[3], 0.5);
+    }
+
+    #[test]
+    fn check_interior_fields() {
+        // The 1 takes exactly one of its three hidden neighbors, the other
+        // two mines spread over the four fields away from it.
+        let ms = Minesweeper::from_ascii("\
+            o . * *\n\
+            * . . .\n", Rules::default()).unwrap();
+
+        let probabilities = ms.mine_probabilities();
+
+        for index in [1, 4, 5] {
+            assert_close(probabilities[index], 1.0 / 3.0);
+        }
+
+        for index in [2, 3, 6, 7] {
+            assert_close(probabilities[index], 0.5);
+        }
+    }
+
+    #[test]
+    fn check_against_brute_force() {
+        let rules = Rules { first_click: FirstClick::SafeCell, ..Rules::default() };
+
+        for seed in 0..30 {
+            let mut ms = Minesweeper::with_seed(5, 4, 5, rules, seed).unwrap();
+            ms.open((2, 2)).unwrap();
+
+            if ms.status().is_over() {
+                continue;
+            }
+
+            let snapshot = ms.snapshot();
+            let hidden: Vec<usize> = (0..snapshot.cells.len()).filter(|&index| snapshot.cells[index] == CellState::Hidden).collect();
+            let mut mines_at = vec![0u64; snapshot.cells.len()];
+            let mut arrangements = 0u64;
+
+            for bits in 0u64..1 << hidden.len() {
+                if bits.count_ones() as usize != ms.mine_count() {
+                    continue;
+                }
+
+                let is_mine = |index: usize| hidden.iter().position(|&field| field == index).is_some_and(|bit| bits & 1 << bit != 0);
+
+                let consistent = snapshot.cells.iter().enumerate().all(|(index, cell)| match cell {
+                    CellState::Open(count) => {
+                        let pos = (index % snapshot.width, index / snapshot.width);
+                        snapshot.neighbors(pos).filter(|&(x, y)| is_mine(y * snapshot.width + x)).count() == *count as usize
+                    }
+                    _ => true,
+                });
+
+                if consistent {
+                    arrangements += 1;
+
+                    for &index in &hidden {
+                        mines_at[index] += is_mine(index) as u64;
+                    }
+                }
+            }
+
+            let probabilities = ms.mine_probabilities();
+
+            for index in 0..snapshot.cells.len() {
+                assert_close(probabilities[index], mines_at[index] as f64 / arrangements as f64);
+            }
+        }
+    }
+}
@@ -1,7 +1,52 @@
-use rand::{thread_rng, Rng};
+use std::fmt::Debug;
 
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Source of randomness used to lay out mines.
+///
+/// Only `next_u64` has to be provided. `below` works on `u64` rather than
+/// `usize` so a given stream yields the same numbers on 32-bit wasm and on
+/// 64-bit native targets.
+pub trait MineRng: Debug + Send {
+    fn next_u64(&mut self) -> u64;
+
+    fn below(&mut self, bound: usize) -> usize {
+        let bound = bound as u64;
+        let zone = u64::MAX - u64::MAX % bound;
+
+        loop {
+            let value = self.next_u64();
+
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SeededRng(ChaCha8Rng);
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng(ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+impl MineRng for SeededRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+}
+
+pub fn random_seed() -> u64 {
+    thread_rng().gen()
+}
+
+#[cfg(test)]
 pub fn random_range(min: usize, max: usize) -> usize {
     let mut rng = thread_rng();
 
     rng.gen_range(min..max)
-}
\ No newline at end of file
+}
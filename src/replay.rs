@@ -0,0 +1,361 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MoveError, ReplayError};
+use crate::history::Action;
+use crate::minesweeper::{GameStatus, Minesweeper, Position, Rules};
+
+pub const REPLAY_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayAction {
+    Play(Action),
+    Undo,
+    Redo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    /// Milliseconds since the first recorded event.
+    pub time_ms: u64,
+    pub action: ReplayAction,
+}
+
+/// Fields that were already open or marked when recording began, as in a
+/// game loaded from a save or drawn with `from_ascii`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StartingFields {
+    pub open: Vec<Position>,
+    pub flagged: Vec<Position>,
+    pub questioned: Vec<Position>,
+}
+
+impl StartingFields {
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty() && self.flagged.is_empty() && self.questioned.is_empty()
+    }
+}
+
+/// Self-contained recording of a game: the mine layout, every action with
+/// its timestamp, and the outcome the actions are expected to lead to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    pub rules: Rules,
+    pub seed: Option<u64>,
+    pub mines: Vec<Position>,
+    pub starting_fields: StartingFields,
+    pub events: Vec<ReplayEvent>,
+    pub status: GameStatus,
+    pub open_fields: usize,
+}
+
+impl Minesweeper {
+    pub(crate) fn record_event(&mut self, action: ReplayAction) {
+        let now = self.time.now_ms();
+        let start = *self.replay_start.get_or_insert(now);
+
+        self.replay_events.push(ReplayEvent {
+            time_ms: now.saturating_sub(start),
+            action,
+        });
+    }
+
+    /// Everything played so far, ready to be shared or verified.
+    pub fn replay(&self) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            width: self.width,
+            height: self.height,
+            rules: self.rules,
+            seed: self.seed,
+            mines: self.mines.iter().collect(),
+            starting_fields: self.starting_fields.clone(),
+            events: self.replay_events.clone(),
+            status: self.status,
+            open_fields: self.open_fields.len(),
+        }
+    }
+}
+
+impl Replay {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("replays always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Replay, ReplayError> {
+        let replay: Replay = serde_json::from_str(json)?;
+
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(replay.version));
+        }
+
+        Ok(replay)
+    }
+
+    /// Board with the recorded layout and starting fields on which no action
+    /// has been played. It keeps the recorded rules, so undo events only
+    /// verify when the game allowed undo.
+    pub fn start(&self) -> Result<Minesweeper, ReplayError> {
+        let StartingFields { open, flagged, questioned } = &self.starting_fields;
+
+        if let Some(&pos) = open.iter().chain(flagged).chain(questioned).find(|&&(x, y)| x >= self.width || y >= self.height) {
+            return Err(ReplayError::FieldOutsideBoard(pos));
+        }
+
+        let mut game = Minesweeper::with_mines(self.width, self.height, &self.mines, self.rules)?;
+        game.preset_fields(open, flagged, questioned);
+
+        Ok(game)
+    }
+
+    /// Replays every event and checks that they lead to the recorded
+    /// outcome, returning the final board.
+    pub fn verify(&self) -> Result<Minesweeper, ReplayError> {
+        let mut game = self.start()?;
+
+        for (index, event) in self.events.iter().enumerate() {
+            apply(&mut game, event.action).map_err(|error| ReplayError::Move { index, error })?;
+        }
+
+        if game.status() != self.status {
+            return Err(ReplayError::OutcomeMismatch { expected: self.status, actual: game.status() });
+        }
+
+        if game.open_fields.len() != self.open_fields {
+            return Err(ReplayError::OpenFieldsMismatch { expected: self.open_fields, actual: game.open_fields.len() });
+        }
+
+        Ok(game)
+    }
+}
+
+fn apply(game: &mut Minesweeper, action: ReplayAction) -> Result<(), MoveError> {
+    match action {
+        ReplayAction::Play(Action::Open(pos)) => game.open(pos).map(drop),
+        ReplayAction::Play(Action::Chord(pos)) => game.chord(pos).map(drop),
+        ReplayAction::Play(Action::ToggleFlag(pos)) => game.toggle_flag(pos).map(drop),
+        ReplayAction::Undo => game.undo().map(drop),
+        ReplayAction::Redo => game.redo().map(drop),
+    }
+}
+
+/// Steps through a replay one event at a time, in either direction, or up
+/// to a point in time for playback at any speed.
+#[derive(Debug)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    game: Minesweeper,
+    position: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Result<ReplayPlayer, ReplayError> {
+        Ok(ReplayPlayer {
+            game: replay.start()?,
+            replay,
+            position: 0,
+        })
+    }
+
+    pub fn game(&self) -> &Minesweeper {
+        &self.game
+    }
+
+    /// Number of events applied so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.replay.events.len()
+    }
+
+    /// Time of the last applied event, or 0 before the first one.
+    pub fn time_ms(&self) -> u64 {
+        self.position.checked_sub(1).map_or(0, |index| self.replay.events[index].time_ms)
+    }
+
+    pub fn step_forward(&mut self) -> Result<Option<ReplayEvent>, ReplayError> {
+        let Some(&event) = self.replay.events.get(self.position) else {
+            return Ok(None);
+        };
+
+        apply(&mut self.game, event.action)
+            .map_err(|error| ReplayError::Move { index: self.position, error })?;
+        self.position += 1;
+
+        Ok(Some(event))
+    }
+
+    pub fn step_backward(&mut self) -> Result<Option<ReplayEvent>, ReplayError> {
+        if self.position == 0 {
+            return Ok(None);
+        }
+
+        let event = self.replay.events[self.position - 1];
+        self.seek(self.position - 1)?;
+
+        Ok(Some(event))
+    }
+
+    /// Moves to the state right after `position` events were applied.
+    pub fn seek(&mut self, position: usize) -> Result<(), ReplayError> {
+        let position = position.min(self.replay.events.len());
+
+        if position < self.position {
+            self.game = self.replay.start()?;
+            self.position = 0;
+        }
+
+        while self.position < position {
+            self.step_forward()?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves to the state at `time_ms` into the recording. Scale the time
+    /// passed in to play back faster or slower than real time.
+    pub fn seek_time(&mut self, time_ms: u64) -> Result<(), ReplayError> {
+        let position = self.replay.events.partition_point(|event| event.time_ms <= time_ms);
+
+        self.seek(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        avf,
+        clock::ManualTime,
+        error::{FormatError, MoveError, ReplayError},
+        history::Action,
+        minesweeper::{FirstClick, GameStatus, Minesweeper, Rules},
+        replay::{Replay, ReplayAction, ReplayPlayer},
+        snapshot::CellState,
+    };
+
+    fn recorded_game() -> Minesweeper {
+        let time = ManualTime::new(1_000);
+        let mut ms = Minesweeper::from_ascii("* . . . * .\n", Rules::default()).unwrap();
+        ms.set_time_source(time.clone());
+
+        ms.open((2, 0)).unwrap();
+        time.advance(500);
+        ms.toggle_flag((4, 0)).unwrap();
+        time.advance(250);
+        ms.toggle_flag((0, 0)).unwrap();
+        time.advance(250);
+        ms.undo().unwrap();
+        time.advance(1_000);
+        ms.open((5, 0)).unwrap();
+
+        ms
+    }
+
+    #[test]
+    fn check_record_and_verify() {
+        let ms = recorded_game();
+        let replay = Replay::from_json(&ms.replay().to_json()).unwrap();
+
+        let times: Vec<u64> = replay.events.iter().map(|event| event.time_ms).collect();
+        assert_eq!(times, [0, 500, 750, 1_000, 2_000]);
+        assert_eq!(replay.events[3].action, ReplayAction::Undo);
+        assert_eq!(replay.status, GameStatus::Won);
+
+        let verified = replay.verify().unwrap();
+        assert_eq!(verified.to_string(), ms.to_string());
+    }
+
+    #[test]
+    fn check_tampered_replay() {
+        let mut replay = recorded_game().replay();
+        replay.events.pop();
+
+        assert!(matches!(replay.verify(), Err(ReplayError::OutcomeMismatch { .. })));
+
+        replay.events.push(replay.events[0]);
+
+        assert!(matches!(replay.verify(), Err(ReplayError::Move { index: 4, .. })));
+
+        let mut replay = recorded_game().replay();
+        replay.open_fields -= 1;
+
+        assert!(matches!(replay.verify(), Err(ReplayError::OpenFieldsMismatch { expected: 3, actual: 4 })));
+    }
+
+    #[test]
+    fn check_replay_of_loaded_game() {
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+        let mut ms = Minesweeper::with_seed(9, 9, 10, rules, 4).unwrap();
+        ms.open((4, 4)).unwrap();
+
+        let mut loaded = Minesweeper::from_json(&ms.to_json()).unwrap();
+        let hidden = (0..81)
+            .map(|index| (index % 9, index / 9))
+            .find(|&pos| loaded.cell(pos) == Some(CellState::Hidden))
+            .unwrap();
+        loaded.toggle_flag(hidden).unwrap();
+
+        let replay = loaded.replay();
+        assert_eq!(replay.starting_fields.open.len(), ms.save().open.len());
+        assert_eq!(replay.verify().unwrap().to_string(), loaded.to_string());
+
+        let mut drawn = Minesweeper::from_ascii("\
+            o o F .\n\
+            o o . .\n\
+            . . . *\n", Rules::default()).unwrap();
+        drawn.open((0, 2)).unwrap();
+
+        let replay = Replay::from_json(&drawn.replay().to_json()).unwrap();
+        assert_eq!(replay.verify().unwrap().to_string(), drawn.to_string());
+        assert_eq!(ReplayPlayer::new(replay).unwrap().game().cell((2, 0)), Some(CellState::Flagged));
+        assert!(matches!(avf::write(&drawn.replay()), Err(FormatError::Unsupported(_))));
+    }
+
+    #[test]
+    fn check_undo_in_ranked_replay() {
+        let mut replay = recorded_game().replay();
+        replay.rules.undo = false;
+
+        assert!(matches!(
+            replay.verify(),
+            Err(ReplayError::Move { index: 3, error: MoveError::UndoDisabled })
+        ));
+    }
+
+    #[test]
+    fn check_player() {
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+        let time = ManualTime::new(0);
+        let mut ms = Minesweeper::with_seed(9, 9, 10, rules, 11).unwrap();
+        ms.set_time_source(time.clone());
+        ms.open((4, 4)).unwrap();
+        time.advance(100);
+
+        let hidden = (0..81)
+            .map(|index| (index % 9, index / 9))
+            .find(|&pos| ms.cell(pos) == Some(CellState::Hidden))
+            .unwrap();
+        ms.toggle_flag(hidden).unwrap();
+
+        let mut player = ReplayPlayer::new(ms.replay()).unwrap();
+
+        assert_eq!(player.step_forward().unwrap().map(|event| event.action), Some(ReplayAction::Play(Action::Open((4, 4)))));
+        let after_open = player.game().to_string();
+
+        player.seek(2).unwrap();
+        assert!(player.is_finished());
+
+        player.step_backward().unwrap();
+        assert_eq!(player.game().to_string(), after_open);
+
+        player.seek_time(99).unwrap();
+        assert_eq!(player.position(), 1);
+
+        player.seek_time(100).unwrap();
+        assert_eq!(player.position(), 2);
+    }
+}
@@ -0,0 +1,321 @@
+//! Viennasweeper replays (`.rmv`).
+//!
+//! Layout handled here, all integers big-endian:
+//!
+//! - the magic bytes `*rmv` and a `u16` file type, 1 for videos
+//! - a size table: `u16` sizes of the result string, version info, player
+//!   info, board, preflags and properties sections, a `u32` video size and
+//!   a `u16` checksum size
+//! - the sections in that order. Player info is a `u16` entry count
+//!   followed by byte-length prefixed strings (name, nickname, country,
+//!   token). The board holds width and height as bytes, the mine count as
+//!   `u16` and one `(column, row)` byte pair per mine, preflags a `u16`
+//!   count and the same pairs. Properties start with the question mark,
+//!   nono, mode and level bytes
+//! - video events of one kind byte: 1 to 7 are mouse events (move, left
+//!   down, left up, right down, right up, middle down, middle up) with a
+//!   `u24` time in milliseconds and `u16` pixel coordinates, 8 to 14 and 18
+//!   to 27 are board events with a `(column, row)` byte pair, and 15 to 17
+//!   end the video
+//!
+//! Result and version strings, player info, board events and the checksum
+//! are skipped, as are bytes a section has beyond the fields read here.
+
+use std::ops::RangeInclusive;
+
+use crate::error::FormatError;
+use crate::minesweeper::{GameStatus, Position, Rules};
+use crate::mouse::{self, MouseEvent, MouseKind, Reader};
+use crate::replay::Replay;
+
+const MAGIC: &[u8; 4] = b"*rmv";
+const VIDEO: u16 = 1;
+const VERSION_INFO: &[u8] = b"minesweeper";
+const CUSTOM: u8 = 3;
+const LEVELS: [(u8, (usize, usize, usize)); 3] = [(0, (8, 8, 10)), (1, (16, 16, 40)), (2, (30, 16, 99))];
+
+const KINDS: [MouseKind; 7] = [
+    MouseKind::Move,
+    MouseKind::LeftDown,
+    MouseKind::LeftUp,
+    MouseKind::RightDown,
+    MouseKind::RightUp,
+    MouseKind::MiddleDown,
+    MouseKind::MiddleUp,
+];
+const BOARD_EVENTS: [RangeInclusive<u8>; 2] = [8..=14, 18..=27];
+const END_EVENTS: RangeInclusive<u8> = 15..=17;
+
+pub fn read(bytes: &[u8]) -> Result<Replay, FormatError> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.take(4)? != MAGIC {
+        return Err(FormatError::BadMagic);
+    }
+
+    if reader.u16()? != VIDEO {
+        return Err(FormatError::Unsupported("rmv files other than videos"));
+    }
+
+    let result_size = reader.u16()? as usize;
+    let version_size = reader.u16()? as usize;
+    let player_size = reader.u16()? as usize;
+    let board_size = reader.u16()? as usize;
+    let preflags_size = reader.u16()? as usize;
+    let properties_size = reader.u16()? as usize;
+    let video_size = reader.u32()? as usize;
+    reader.u16()?;
+
+    reader.take(result_size + version_size + player_size)?;
+
+    let mut board = Reader::new(reader.take(board_size)?);
+    let width = board.u8()? as usize;
+    let height = board.u8()? as usize;
+    let mines = positions(&mut board)?;
+
+    let mut preflags = Reader::new(reader.take(preflags_size)?);
+    let preflags = if preflags.is_empty() { Vec::new() } else { positions(&mut preflags)? };
+
+    let mut properties = Reader::new(reader.take(properties_size)?);
+    let rules = Rules { question_marks: !properties.is_empty() && properties.u8()? != 0, ..Rules::default() };
+
+    let mut video = Reader::new(reader.take(video_size)?);
+    let mut events = Vec::new();
+
+    loop {
+        let kind = video.u8()?;
+
+        if let Some(&kind) = KINDS.get((kind as usize).wrapping_sub(1)) {
+            let time_ms = video.u24()? as u64;
+            let x = video.u16()?;
+            let y = video.u16()?;
+
+            events.push(MouseEvent { kind, time_ms, x, y });
+        } else if BOARD_EVENTS.iter().any(|kinds| kinds.contains(&kind)) {
+            video.take(2)?;
+        } else if END_EVENTS.contains(&kind) {
+            break;
+        } else {
+            return Err(FormatError::Invalid("unknown video event"));
+        }
+    }
+
+    mouse::to_replay(width, height, mines, &preflags, rules, &events)
+}
+
+/// A `u16` count followed by one `(column, row)` byte pair per field.
+fn positions(reader: &mut Reader) -> Result<Vec<Position>, FormatError> {
+    let count = reader.u16()? as usize;
+    let mut positions = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let x = reader.u8()? as usize;
+        let y = reader.u8()? as usize;
+        positions.push((x, y));
+    }
+
+    Ok(positions)
+}
+
+fn write_positions(bytes: &mut Vec<u8>, positions: &[Position]) {
+    bytes.extend((positions.len() as u16).to_be_bytes());
+
+    for &(x, y) in positions {
+        bytes.extend([x as u8, y as u8]);
+    }
+}
+
+pub fn write(replay: &Replay) -> Result<Vec<u8>, FormatError> {
+    let preflags = &replay.starting_fields.flagged;
+
+    if replay.width > 255 || replay.height > 255 {
+        return Err(FormatError::Unsupported("boards larger than 255x255"));
+    }
+
+    let result: &[u8] = match replay.status {
+        GameStatus::Won => b"won",
+        GameStatus::Lost => b"lost",
+        GameStatus::NotStarted | GameStatus::Playing => b"unfinished",
+    };
+
+    let level = LEVELS.iter()
+        .find(|(_, board)| *board == (replay.width, replay.height, replay.mines.len()))
+        .map_or(CUSTOM, |&(level, _)| level);
+
+    let mut board = vec![replay.width as u8, replay.height as u8];
+    write_positions(&mut board, &replay.mines);
+
+    let mut preflag_bytes = Vec::new();
+    if !preflags.is_empty() {
+        write_positions(&mut preflag_bytes, preflags);
+    }
+
+    // Sections are sized by a `u16`, which caps mines and preflags at 32765.
+    if board.len().max(preflag_bytes.len()) > u16::MAX as usize {
+        return Err(FormatError::Unsupported("more than 32765 mines or preflags"));
+    }
+
+    let properties = [replay.rules.question_marks as u8, 0, 0, level];
+
+    let mut video = Vec::new();
+
+    for event in mouse::from_replay(replay)? {
+        let kind = KINDS.iter().position(|&kind| kind == event.kind).unwrap() as u8 + 1;
+        let time_ms = event.time_ms.min(0xff_ffff) as u32;
+
+        video.push(kind);
+        video.extend(&time_ms.to_be_bytes()[1..]);
+        video.extend(event.x.to_be_bytes());
+        video.extend(event.y.to_be_bytes());
+    }
+
+    video.push(*END_EVENTS.start());
+
+    // The player info section is an empty entry list.
+    let player: &[u8] = &[0, 0];
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VIDEO.to_be_bytes());
+
+    for section in [result, VERSION_INFO, player, &board, &preflag_bytes, &properties] {
+        bytes.extend((section.len() as u16).to_be_bytes());
+    }
+
+    bytes.extend((video.len() as u32).to_be_bytes());
+    bytes.extend(0u16.to_be_bytes());
+
+    for section in [result, VERSION_INFO, player, &board, &preflag_bytes, &properties, &video] {
+        bytes.extend(section);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::ManualTime,
+        error::FormatError,
+        history::Action,
+        minesweeper::{GameStatus, Minesweeper, Rules},
+        replay::ReplayAction,
+        rmv,
+    };
+
+    /// Video file with the given sections and checksum, sized the way
+    /// Viennasweeper writes them.
+    fn video_file(sections: [&[u8]; 7], checksum: &[u8]) -> Vec<u8> {
+        let mut bytes = b"*rmv\0\x01".to_vec();
+
+        for section in &sections[..6] {
+            bytes.extend((section.len() as u16).to_be_bytes());
+        }
+
+        bytes.extend((sections[6].len() as u32).to_be_bytes());
+        bytes.extend((checksum.len() as u16).to_be_bytes());
+        bytes.extend(sections.concat());
+        bytes.extend(checksum);
+        bytes
+    }
+
+    fn mouse_event(kind: u8, time_ms: u32, x: u16, y: u16) -> Vec<u8> {
+        [&[kind][..], &time_ms.to_be_bytes()[1..], &x.to_be_bytes(), &y.to_be_bytes()].concat()
+    }
+
+    #[test]
+    fn check_round_trip_with_chord() {
+        let time = ManualTime::new(0);
+        let mut ms = Minesweeper::with_mines(4, 3, &[(0, 0), (3, 2)], Rules::default()).unwrap();
+        ms.set_time_source(time.clone());
+
+        ms.open((1, 1)).unwrap();
+        time.advance(700);
+        ms.toggle_flag((0, 0)).unwrap();
+        time.advance(300);
+        ms.chord((1, 1)).unwrap();
+
+        let replay = ms.replay();
+        let read = rmv::read(&rmv::write(&replay).unwrap()).unwrap();
+
+        assert_eq!(read.mines, replay.mines);
+        assert_eq!(read.events, replay.events);
+        assert_eq!(read.events[2].action, ReplayAction::Play(Action::Chord((1, 1))));
+        assert_eq!(read.status, ms.status());
+        assert_eq!(read.open_fields, replay.open_fields);
+    }
+
+    #[test]
+    fn check_preflags_and_properties() {
+        let rules = Rules { question_marks: true, ..Rules::default() };
+        let mut ms = Minesweeper::from_ascii("\
+            F . . .\n\
+            . . . *\n", rules).unwrap();
+        ms.open((1, 0)).unwrap();
+
+        let replay = ms.replay();
+        let read = rmv::read(&rmv::write(&replay).unwrap()).unwrap();
+
+        assert_eq!(read.starting_fields, replay.starting_fields);
+        assert!(read.rules.question_marks);
+        assert_eq!(read.verify().unwrap().to_string(), ms.to_string());
+    }
+
+    #[test]
+    fn check_sized_sections() {
+        let player = [&[0, 2, 3][..], b"Ann", &[2], b"AT"].concat();
+        // A 3x1 board with a mine on the right and a flag on it.
+        let board = [3, 1, 0, 1, 2, 0];
+        let preflags = [0, 1, 2, 0];
+        // Question marks off, then nono, mode and level, and a byte this
+        // reader does not know.
+        let properties = [0, 0, 0, 3, 7];
+        let video = [
+            mouse_event(1, 0, 40, 8),
+            mouse_event(2, 1_500, 8, 8),
+            mouse_event(3, 1_620, 8, 8),
+            // Board events for the two fields the release opened.
+            vec![18, 0, 0, 19, 1, 0],
+            vec![15],
+        ].concat();
+
+        let bytes = video_file([b"Won", b"Viennasweeper 3.1", &player, &board, &preflags, &properties, &video], &[0xab; 4]);
+        let replay = rmv::read(&bytes).unwrap();
+
+        assert_eq!((replay.width, replay.height), (3, 1));
+        assert_eq!(replay.mines, [(2, 0)]);
+        assert_eq!(replay.starting_fields.flagged, [(2, 0)]);
+        assert!(!replay.rules.question_marks);
+        assert_eq!(replay.events.len(), 1);
+        assert_eq!(replay.events[0].time_ms, 1_620);
+        assert_eq!(replay.status, GameStatus::Won);
+        assert!(replay.verify().is_ok());
+
+        let no_preflags = video_file([b"", b"", &[0, 0], &board, b"", b"", &video], b"");
+        assert!(rmv::read(&no_preflags).unwrap().starting_fields.is_empty());
+    }
+
+    #[test]
+    fn check_invalid_files() {
+        let board = [2, 1, 0, 0];
+        let end = mouse_event(2, 0, 0, 0);
+
+        assert!(matches!(rmv::read(b"*avf"), Err(FormatError::BadMagic)));
+        assert!(matches!(rmv::read(b"*rmv\0\x01\0"), Err(FormatError::UnexpectedEof)));
+        assert!(matches!(
+            rmv::read(&video_file([b"", b"", b"", &board, b"", b"", &[&end[..], &[42]].concat()], b"")),
+            Err(FormatError::Invalid(_))
+        ));
+        assert!(matches!(rmv::read(&video_file([b"", b"", b"", &board, b"", b"", &end], b"")), Err(FormatError::UnexpectedEof)));
+        assert!(matches!(
+            rmv::read(&video_file([b"", b"", b"", &board, &[0, 1, 2, 0], b"", &[15]], b"")),
+            Err(FormatError::Invalid(_))
+        ));
+
+        let mut ms = Minesweeper::new(3, 3, 0).unwrap();
+        ms.toggle_flag((0, 0)).unwrap();
+        ms.undo().unwrap();
+
+        assert!(matches!(rmv::write(&ms.replay()), Err(FormatError::Unsupported(_))));
+        assert_eq!(ms.status(), GameStatus::NotStarted);
+    }
+}
@@ -0,0 +1,282 @@
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::BoardStats;
+use crate::clock::{Clock, SystemTime};
+use crate::error::LoadError;
+use crate::field_set::FieldSet;
+use crate::minesweeper::{validate, GameStatus, Mark, Minesweeper, Position, Rules};
+use crate::random::{random_seed, SeededRng};
+use crate::replay::StartingFields;
+use crate::stats::Clicks;
+
+/// Version written by `Minesweeper::save`. Binary saves are positional, so
+/// bump it whenever a field is added to `SavedGame` or anything in it, such
+/// as `Rules`. Saves of any other version are rejected, not migrated.
+pub const SAVE_VERSION: u32 = 4;
+
+/// Complete, self-contained state of a game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    pub mine_count: usize,
+    pub rules: Rules,
+    pub seed: Option<u64>,
+    /// `None` while mines are still waiting for the first click.
+    pub mines: Option<Vec<Position>>,
+    pub open: Vec<Position>,
+    pub flagged: Vec<Position>,
+    /// Hidden fields with a question mark.
+    pub questioned: Vec<Position>,
+    pub status: GameStatus,
+    pub moves: usize,
+    pub hints: usize,
+    pub clicks: Clicks,
+    pub elapsed_ms: u64,
+    /// Measures of the layout, kept once the game is over so finished games
+    /// can be rated without replaying them.
+    pub board_stats: Option<BoardStats>,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+fn binary() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+impl Minesweeper {
+    pub fn save(&self) -> SavedGame {
+        let marked = |mark: Mark| {
+            let mut fields: Vec<Position> = self.marks.iter()
+                .filter(|&(pos, &marked)| marked == mark && !self.open_fields.contains(pos))
+                .map(|(&pos, _)| pos)
+                .collect();
+            fields.sort_unstable_by_key(|&(x, y)| (y, x));
+            fields
+        };
+
+        SavedGame {
+            version: SAVE_VERSION,
+            width: self.width,
+            height: self.height,
+            mine_count: self.mine_count,
+            rules: self.rules,
+            seed: self.seed,
+            mines: self.mines_placed.then(|| self.mines.iter().collect()),
+            open: self.open_fields.iter().collect(),
+            flagged: marked(Mark::Flag),
+            questioned: marked(Mark::Question),
+            status: self.status,
+            moves: self.moves,
+            hints: self.hints,
+            clicks: self.clicks,
+            elapsed_ms: self.elapsed_ms(),
+            board_stats: self.status.is_over().then(|| self.board_stats()).flatten(),
+        }
+    }
+
+    pub fn restore(saved: SavedGame) -> Result<Minesweeper, LoadError> {
+        if saved.version != SAVE_VERSION {
+            return Err(LoadError::UnsupportedVersion(saved.version));
+        }
+
+        let SavedGame { width, height, mine_count, rules, seed, .. } = saved;
+
+        validate(width, height, mine_count, rules)?;
+
+        let in_bounds = |fields: &[Position]| fields.iter().all(|&(x, y)| x < width && y < height);
+
+        if !in_bounds(&saved.open) || !in_bounds(&saved.flagged) || !in_bounds(&saved.questioned) {
+            return Err(LoadError::InvalidState("field outside of the board"));
+        }
+
+        let mut mines = FieldSet::new(width, height);
+
+        if let Some(positions) = &saved.mines {
+            if !in_bounds(positions) {
+                return Err(LoadError::InvalidState("mine outside of the board"));
+            }
+
+            positions.iter().for_each(|&pos| { mines.insert(pos); });
+
+            if mines.len() != mine_count {
+                return Err(LoadError::InvalidState("mine count does not match the mines"));
+            }
+        } else if !saved.open.is_empty() {
+            return Err(LoadError::InvalidState("open fields without mines"));
+        }
+
+        let mut open_fields = FieldSet::new(width, height);
+        saved.open.iter().for_each(|&pos| { open_fields.insert(pos); });
+
+        Ok(Minesweeper {
+            width,
+            height,
+            mine_count,
+            rules,
+            seed,
+            rng: Box::new(SeededRng::new(seed.unwrap_or_else(random_seed))),
+            open_fields,
+            mines,
+            mines_placed: saved.mines.is_some(),
+            marks: saved.questioned.iter()
+                .map(|&pos| (pos, Mark::Question))
+                .chain(saved.flagged.iter().map(|&pos| (pos, Mark::Flag)))
+                .collect(),
+            status: saved.status,
+            moves: saved.moves,
+            hints: saved.hints,
+            clicks: saved.clicks,
+            clock: Clock::with_elapsed(saved.elapsed_ms),
+            move_log: Vec::new(),
+            redo_log: Vec::new(),
+            time: Box::new(SystemTime),
+            replay_start: None,
+            replay_events: Vec::new(),
+            starting_fields: StartingFields {
+                open: saved.open,
+                flagged: saved.flagged,
+                questioned: saved.questioned,
+            },
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.save()).expect("saved games always serialize")
+    }
+
+    pub fn from_json(json: &str) -> Result<Minesweeper, LoadError> {
+        let header: Header = serde_json::from_str(json)?;
+
+        if header.version != SAVE_VERSION {
+            return Err(LoadError::UnsupportedVersion(header.version));
+        }
+
+        Minesweeper::restore(serde_json::from_str(json)?)
+    }
+
+    /// Compact binary encoding of `save`, using variable length integers.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        binary().serialize(&self.save()).expect("saved games always serialize")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Minesweeper, LoadError> {
+        let header: Header = binary().allow_trailing_bytes().deserialize(bytes)?;
+
+        if header.version != SAVE_VERSION {
+            return Err(LoadError::UnsupportedVersion(header.version));
+        }
+
+        Minesweeper::restore(binary().deserialize(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bincode::Options;
+
+    use crate::{
+        clock::ManualTime,
+        error::{BoardError, LoadError},
+        minesweeper::{FirstClick, GameStatus, Minesweeper, Rules},
+        save::{binary, SavedGame, SAVE_VERSION},
+    };
+
+    fn rules() -> Rules {
+        Rules { first_click: FirstClick::SafeNeighborhood, question_marks: true, ..Rules::default() }
+    }
+
+    #[test]
+    fn check_round_trip() {
+        let mut ms = Minesweeper::with_seed(12, 9, 15, rules(), 99).unwrap();
+        ms.set_time_source(ManualTime::new(0));
+
+        ms.open((6, 4)).unwrap();
+        let _ = ms.toggle_flag((0, 0));
+        let _ = ms.toggle_flag((11, 8));
+        let _ = ms.toggle_flag((11, 8));
+
+        let from_json = Minesweeper::from_json(&ms.to_json()).unwrap();
+        let from_bytes = Minesweeper::from_bytes(&ms.to_bytes()).unwrap();
+
+        assert_eq!(from_json.save(), ms.save());
+        assert_eq!(from_bytes.save(), ms.save());
+        assert_eq!(from_json.to_string(), ms.to_string());
+        assert_eq!(from_json.moves(), ms.moves());
+        assert!(ms.to_bytes().len() < ms.to_json().len());
+    }
+
+    #[test]
+    fn check_unplaced_mines_keep_seed() {
+        let mut ms = Minesweeper::with_seed(12, 9, 15, rules(), 5).unwrap();
+        let mut restored = Minesweeper::from_json(&ms.to_json()).unwrap();
+        ms.set_time_source(ManualTime::new(0));
+        restored.set_time_source(ManualTime::new(0));
+
+        assert_eq!(restored.save().mines, None);
+
+        ms.open((2, 2)).unwrap();
+        restored.open((2, 2)).unwrap();
+
+        assert_eq!(restored.save(), ms.save());
+        assert_ne!(restored.status(), GameStatus::NotStarted);
+    }
+
+    #[test]
+    fn check_older_versions_are_rejected() {
+        let ms = Minesweeper::new(4, 4, 3).unwrap();
+
+        for version in 1..SAVE_VERSION {
+            let saved = SavedGame { version, ..ms.save() };
+            let bytes = binary().serialize(&saved).unwrap();
+
+            assert!(matches!(Minesweeper::from_bytes(&bytes), Err(LoadError::UnsupportedVersion(v)) if v == version));
+            assert!(matches!(
+                Minesweeper::from_json(&serde_json::to_string(&saved).unwrap()),
+                Err(LoadError::UnsupportedVersion(v)) if v == version
+            ));
+        }
+    }
+
+    #[test]
+    fn check_invalid_saves() {
+        let ms = Minesweeper::new(4, 4, 3).unwrap();
+
+        let mut saved = ms.save();
+        saved.version = SAVE_VERSION + 1;
+        let json = serde_json::to_string(&saved).unwrap();
+        assert!(matches!(Minesweeper::from_json(&json), Err(LoadError::UnsupportedVersion(_))));
+
+        let mut saved = ms.save();
+        saved.open.push((4, 0));
+        assert!(matches!(Minesweeper::restore(saved), Err(LoadError::InvalidState(_))));
+
+        let mut saved = ms.save();
+        saved.mine_count = 2;
+        assert!(matches!(Minesweeper::restore(saved), Err(LoadError::InvalidState(_))));
+
+        let mut saved = ms.save();
+        saved.width = usize::MAX;
+        let json = serde_json::to_string(&saved).unwrap();
+        assert!(matches!(Minesweeper::from_json(&json), Err(LoadError::Board(BoardError::TooManyCells { .. }))));
+
+        let mut saved = ms.save();
+        saved.width = 1 << 20;
+        saved.height = 1 << 20;
+        assert!(matches!(Minesweeper::restore(saved), Err(LoadError::Board(_))));
+
+        let mut saved = ms.save();
+        saved.elapsed_ms = u64::MAX;
+        let mut restored = Minesweeper::restore(saved).unwrap();
+        restored.open((0, 0)).unwrap();
+        assert_eq!(restored.elapsed_ms(), u64::MAX);
+
+        assert!(matches!(Minesweeper::from_json("{}"), Err(LoadError::Json(_))));
+        assert!(matches!(Minesweeper::from_bytes(&[SAVE_VERSION as u8]), Err(LoadError::Binary(_))));
+    }
+}
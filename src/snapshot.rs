@@ -0,0 +1,89 @@
+use crate::minesweeper::{neighbors, GameStatus, Position};
+
+/// What a player can see of a single field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellState {
+    Hidden,
+    Flagged,
+    /// Open field with its number of neighboring mines.
+    Open(u8),
+    /// Mine uncovered at the end of a lost game.
+    Mine,
+    /// The mine that was opened and lost the game.
+    ExplodedMine,
+    /// Flag on a field without a mine, shown once the game is lost.
+    WrongFlag,
+    /// Hidden field with a question mark.
+    Questioned,
+}
+
+impl CellState {
+    pub const HIDDEN: u8 = 9;
+    pub const FLAGGED: u8 = 10;
+    pub const MINE: u8 = 11;
+    pub const EXPLODED_MINE: u8 = 12;
+    pub const WRONG_FLAG: u8 = 13;
+    pub const QUESTIONED: u8 = 14;
+
+    /// Compact encoding: `0..=8` for open fields, the constants above for
+    /// everything else.
+    pub fn code(self) -> u8 {
+        match self {
+            CellState::Open(count) => count,
+            CellState::Hidden => CellState::HIDDEN,
+            CellState::Flagged => CellState::FLAGGED,
+            CellState::Mine => CellState::MINE,
+            CellState::ExplodedMine => CellState::EXPLODED_MINE,
+            CellState::WrongFlag => CellState::WRONG_FLAG,
+            CellState::Questioned => CellState::QUESTIONED,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<CellState> {
+        match code {
+            0..=8 => Some(CellState::Open(code)),
+            CellState::HIDDEN => Some(CellState::Hidden),
+            CellState::FLAGGED => Some(CellState::Flagged),
+            CellState::MINE => Some(CellState::Mine),
+            CellState::EXPLODED_MINE => Some(CellState::ExplodedMine),
+            CellState::WRONG_FLAG => Some(CellState::WrongFlag),
+            CellState::QUESTIONED => Some(CellState::Questioned),
+            _ => None,
+        }
+    }
+
+    /// Whether the field is still covered, question marks included.
+    pub fn is_hidden(self) -> bool {
+        matches!(self, CellState::Hidden | CellState::Questioned)
+    }
+}
+
+/// Player-visible state of a whole board, stored row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub width: usize,
+    pub height: usize,
+    pub status: GameStatus,
+    pub elapsed_ms: u64,
+    /// Whether fields on opposite edges are neighbors.
+    pub wrap_around: bool,
+    pub cells: Vec<CellState>,
+}
+
+impl Snapshot {
+    pub fn get(&self, (x, y): Position) -> Option<CellState> {
+        if x < self.width && y < self.height {
+            Some(self.cells[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn neighbors(&self, pos: Position) -> impl Iterator<Item=Position> {
+        neighbors(self.width, self.height, self.wrap_around, pos)
+    }
+
+    pub fn codes(&self) -> Vec<u8> {
+        self.cells.iter().map(|cell| cell.code()).collect()
+    }
+}
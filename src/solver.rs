@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use crate::minesweeper::{Minesweeper, Position};
+use crate::snapshot::{CellState, Snapshot};
+
+/// The rule that proved a deduction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// The number at `cell` is already satisfied by known mines, or needs
+    /// every one of its unknown neighbors to be a mine.
+    Single { cell: Position },
+    /// The unknown neighbors of `inner` are a subset of those of `outer`, so
+    /// the rest of `outer`'s neighbors hold the difference of their counts.
+    Subset { inner: Position, outer: Position },
+    /// The numbers at `cell` and `other` share some unknown neighbors, which
+    /// bounds how many mines the neighbors of `cell` outside of `other` hold.
+    Overlap { cell: Position, other: Position },
+    /// Every remaining mine is accounted for, or every unknown field must be
+    /// one.
+    MineCount,
+}
+
+/// A field that is provably safe or provably a mine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deduction {
+    pub position: Position,
+    pub mine: bool,
+    pub reason: Reason,
+}
+
+/// An open number together with its neighbors that are not known yet.
+#[derive(Debug)]
+pub(crate) struct Constraint {
+    pub(crate) cell: Position,
+    pub(crate) fields: Vec<Position>,
+    pub(crate) mines: usize,
+}
+
+/// Deduces every field that follows from what the player can see, given the
+/// total number of mines on the board.
+///
+/// Flags are trusted to be mines. Deductions are returned in the order they
+/// were found, later ones possibly relying on earlier ones.
+pub fn solve(snapshot: &Snapshot, mine_count: usize) -> Vec<Deduction> {
+    let mut deductions = Vec::new();
+
+    if snapshot.status.is_over() {
+        return deductions;
+    }
+
+    let mut known = known_fields(snapshot);
+
+    loop {
+        let constraints = constraints(snapshot, &known);
+        let mut found = single(&constraints);
+
+        if found.is_empty() {
+            found = pairs(&constraints);
+        }
+
+        if found.is_empty() {
+            found = by_mine_count(snapshot, &known, mine_count);
+        }
+
+        if found.is_empty() {
+            return deductions;
+        }
+
+        for deduction in found {
+            let (x, y) = deduction.position;
+            let field = &mut known[y * snapshot.width + x];
+
+            if field.is_none() {
+                *field = Some(deduction.mine);
+                deductions.push(deduction);
+            }
+        }
+    }
+}
+
+impl Minesweeper {
+    /// Fields that are certainly safe or certainly mines, see `solver::solve`.
+    pub fn deductions(&self) -> Vec<Deduction> {
+        solve(&self.snapshot(), self.mine_count)
+    }
+}
+
+/// Whether each field is known to be a mine, with flags taken at their word.
+pub(crate) fn known_fields(snapshot: &Snapshot) -> Vec<Option<bool>> {
+    snapshot.cells.iter()
+        .map(|cell| match cell {
+            CellState::Hidden | CellState::Questioned => None,
+            CellState::Flagged | CellState::Mine | CellState::ExplodedMine => Some(true),
+            CellState::Open(_) | CellState::WrongFlag => Some(false),
+        })
+        .collect()
+}
+
+pub(crate) fn constraints(snapshot: &Snapshot, known: &[Option<bool>]) -> Vec<Constraint> {
+    let mut constraints = Vec::new();
+
+    for (index, cell) in snapshot.cells.iter().enumerate() {
+        let CellState::Open(count) = *cell else {
+            continue;
+        };
+
+        let cell = (index % snapshot.width, index / snapshot.width);
+        let mut fields = Vec::new();
+        let mut mines = 0;
+
+        for (x, y) in snapshot.neighbors(cell) {
+            match known[y * snapshot.width + x] {
+                None => fields.push((x, y)),
+                Some(true) => mines += 1,
+                Some(false) => {}
+            }
+        }
+
+        // Misplaced flags can make a number unsatisfiable, nothing follows
+        // from it then.
+        let Some(mines) = (count as usize).checked_sub(mines) else {
+            continue;
+        };
+
+        if !fields.is_empty() && mines <= fields.len() {
+            constraints.push(Constraint { cell, fields, mines });
+        }
+    }
+
+    constraints
+}
+
+fn single(constraints: &[Constraint]) -> Vec<Deduction> {
+    constraints.iter()
+        .filter(|constraint| constraint.mines == 0 || constraint.mines == constraint.fields.len())
+        .flat_map(|constraint| constraint.fields.iter().map(|&position| Deduction {
+            position,
+            mine: constraint.mines > 0,
+            reason: Reason::Single { cell: constraint.cell },
+        }))
+        .collect()
+}
+
+fn pairs(constraints: &[Constraint]) -> Vec<Deduction> {
+    let mut by_field: HashMap<Position, Vec<usize>> = HashMap::new();
+
+    for (index, constraint) in constraints.iter().enumerate() {
+        for &field in &constraint.fields {
+            by_field.entry(field).or_default().push(index);
+        }
+    }
+
+    let mut found = Vec::new();
+
+    for (index, a) in constraints.iter().enumerate() {
+        let mut others: Vec<usize> = a.fields.iter()
+            .flat_map(|field| by_field[field].iter().copied())
+            .filter(|&other| other != index)
+            .collect();
+
+        others.sort_unstable();
+        others.dedup();
+
+        for b in others.into_iter().map(|other| &constraints[other]) {
+            let only_a: Vec<Position> = a.fields.iter().copied().filter(|field| !b.fields.contains(field)).collect();
+
+            if only_a.is_empty() {
+                continue;
+            }
+
+            let shared = a.fields.len() - only_a.len();
+            let only_b = b.fields.len() - shared;
+
+            // Bounds on the number of mines among the shared fields.
+            let low = a.mines.saturating_sub(only_a.len()).max(b.mines.saturating_sub(only_b));
+            let high = a.mines.min(b.mines).min(shared);
+
+            if low > high {
+                continue;
+            }
+
+            let mine = if a.mines - low == 0 {
+                false
+            } else if a.mines - high == only_a.len() {
+                true
+            } else {
+                continue;
+            };
+
+            let reason = if only_b == 0 {
+                Reason::Subset { inner: b.cell, outer: a.cell }
+            } else {
+                Reason::Overlap { cell: a.cell, other: b.cell }
+            };
+
+            found.extend(only_a.into_iter().map(|position| Deduction { position, mine, reason }));
+        }
+    }
+
+    found
+}
+
+fn by_mine_count(snapshot: &Snapshot, known: &[Option<bool>], mine_count: usize) -> Vec<Deduction> {
+    let known_mines = known.iter().filter(|&&field| field == Some(true)).count();
+    let unknown: Vec<Position> = known.iter()
+        .enumerate()
+        .filter(|(_, field)| field.is_none())
+        .map(|(index, _)| (index % snapshot.width, index / snapshot.width))
+        .collect();
+
+    let Some(remaining) = mine_count.checked_sub(known_mines) else {
+        return Vec::new();
+    };
+
+    if unknown.is_empty() || (remaining != 0 && remaining != unknown.len()) {
+        return Vec::new();
+    }
+
+    unknown.into_iter()
+        .map(|position| Deduction { position, mine: remaining > 0, reason: Reason::MineCount })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        minesweeper::{FirstClick, GameStatus, Minesweeper, Rules},
+        solver::{Deduction, Reason},
+    };
+
+    fn board(map: &str) -> Minesweeper {
+        Minesweeper::from_ascii(map, Rules::default()).unwrap()
+    }
+
+    #[test]
+    fn check_single() {
+        let ms = board("\
+            F o\n\
+            . .\n");
+
+        let deductions = ms.deductions();
+
+        assert_eq!(deductions, vec![
+            Deduction { position: (0, 1), mine: false, reason: Reason::Single { cell: (1, 0) } },
+            Deduction { position: (1, 1), mine: false, reason: Reason::Single { cell: (1, 0) } },
+        ]);
+    }
+
+    #[test]
+    fn check_subset_then_single() {
+        let ms = board("\
+            o o o\n\
+            o o o\n\
+            . * .\n");
+
+        let deductions = ms.deductions();
+
+        assert_eq!(deductions, vec![
+            Deduction { position: (2, 2), mine: false, reason: Reason::Subset { inner: (0, 1), outer: (1, 1) } },
+            Deduction { position: (0, 2), mine: false, reason: Reason::Subset { inner: (2, 1), outer: (1, 1) } },
+            Deduction { position: (1, 2), mine: true, reason: Reason::Single { cell: (0, 1) } },
+        ]);
+    }
+
+    #[test]
+    fn check_overlap() {
+        let ms = board("\
+            . . * *\n\
+            . o o *\n");
+
+        let deductions = ms.deductions();
+
+        assert_eq!(deductions, vec![
+            Deduction { position: (0, 0), mine: false, reason: Reason::Overlap { cell: (1, 1), other: (2, 1) } },
+            Deduction { position: (0, 1), mine: false, reason: Reason::Overlap { cell: (1, 1), other: (2, 1) } },
+            Deduction { position: (3, 0), mine: true, reason: Reason::Overlap { cell: (2, 1), other: (1, 1) } },
+            Deduction { position: (3, 1), mine: true, reason: Reason::Overlap { cell: (2, 1), other: (1, 1) } },
+        ]);
+    }
+
+    #[test]
+    fn check_mine_count() {
+        let ms = board("\
+            F .\n\
+            . .\n");
+
+        let deductions = ms.deductions();
+
+        assert_eq!(deductions.len(), 3);
+        assert!(deductions.iter().all(|deduction| !deduction.mine && deduction.reason == Reason::MineCount));
+    }
+
+    #[test]
+    fn check_deductions_are_sound() {
+        let rules = Rules { first_click: FirstClick::SafeNeighborhood, ..Rules::default() };
+
+        for seed in 0..20 {
+            let mut ms = Minesweeper::with_seed(16, 16, 40, rules, seed).unwrap();
+            ms.open((8, 8)).unwrap();
+
+            while !ms.status().is_over() {
+                let deductions = ms.deductions();
+
+                if deductions.is_empty() {
+                    break;
+                }
+
+                for deduction in deductions {
+                    if ms.status().is_over() {
+                        break;
+                    }
+
+                    assert_eq!(ms.mines.contains(&deduction.position), deduction.mine, "{:?}", deduction);
+
+                    if deduction.mine {
+                        ms.toggle_flag(deduction.position).unwrap();
+                    } else if !ms.open_fields.contains(&deduction.position) {
+                        ms.open(deduction.position).unwrap();
+                    }
+                }
+            }
+
+            assert_ne!(ms.status(), GameStatus::Lost);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+use crate::minesweeper::Minesweeper;
+
+/// Clicks made so far, including the ones that failed or changed nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clicks {
+    pub left: usize,
+    pub right: usize,
+    pub chord: usize,
+    /// Clicks on open or flagged fields, chords that opened nothing and
+    /// flags that were taken back, together with their removal.
+    pub wasted: usize,
+}
+
+impl Clicks {
+    pub fn total(&self) -> usize {
+        self.left + self.right + self.chord
+    }
+}
+
+/// How efficiently a finished game was played.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsReport {
+    pub elapsed_ms: u64,
+    pub clicks: Clicks,
+    pub hints: usize,
+    pub bbbv: usize,
+    /// 3BV cleared before the game ended, all of it for a won game.
+    pub solved_bbbv: usize,
+    pub bbbv_per_second: f64,
+    /// Index of efficiency: solved 3BV per click.
+    pub ioe: f64,
+    /// Share of clicks that were not wasted.
+    pub correctness: f64,
+    /// Solved 3BV per click that was not wasted.
+    pub throughput: f64,
+}
+
+impl Minesweeper {
+    /// Clicks since the game was created. Undo does not take them back.
+    pub fn clicks(&self) -> Clicks {
+        self.clicks
+    }
+
+    /// Report on the game once it is over.
+    pub fn stats(&self) -> Option<StatsReport> {
+        if !self.status.is_over() {
+            return None;
+        }
+
+        let elapsed_ms = self.elapsed_ms();
+        let bbbv = self.board_stats().map_or(0, |stats| stats.bbbv);
+        let solved_bbbv = self.solved_bbbv();
+        let clicks = self.clicks;
+        let useful = clicks.total().saturating_sub(clicks.wasted);
+
+        Some(StatsReport {
+            elapsed_ms,
+            clicks,
+            hints: self.hints,
+            bbbv,
+            solved_bbbv,
+            bbbv_per_second: ratio(solved_bbbv as f64 * 1000.0, elapsed_ms as f64),
+            ioe: ratio(solved_bbbv as f64, clicks.total() as f64),
+            correctness: ratio(useful as f64, clicks.total() as f64),
+            throughput: ratio(solved_bbbv as f64, useful as f64),
+        })
+    }
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator == 0.0 {
+        0.0
+    } else {
+        numerator / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::ManualTime,
+        error::MoveError,
+        minesweeper::{Chord, GameStatus, Minesweeper, Rules},
+        stats::Clicks,
+    };
+
+    #[test]
+    fn check_report() {
+        let time = ManualTime::new(0);
+        let mut ms = Minesweeper::from_ascii("\
+            * . . .\n\
+            . . . .\n\
+            . . . *\n", Rules::default()).unwrap();
+        ms.set_time_source(time.clone());
+
+        ms.toggle_flag((1, 1)).unwrap();
+        ms.toggle_flag((1, 1)).unwrap();
+        time.advance(2000);
+        ms.open((1, 0)).unwrap();
+        assert_eq!(ms.open((1, 0)).err(), Some(MoveError::AlreadyOpen));
+        assert_eq!(ms.stats(), None);
+
+        time.advance(2000);
+        ms.open((3, 0)).unwrap();
+        time.advance(1000);
+        ms.open((0, 2)).unwrap();
+        assert_eq!(ms.status(), GameStatus::Won);
+
+        let stats = ms.stats().unwrap();
+
+        assert_eq!(stats.clicks, Clicks { left: 4, right: 2, chord: 0, wasted: 3 });
+        assert_eq!(stats.elapsed_ms, 3000);
+        assert_eq!((stats.bbbv, stats.solved_bbbv), (2, 2));
+        assert_eq!(stats.bbbv_per_second, 2.0 / 3.0);
+        assert_eq!(stats.ioe, 2.0 / 6.0);
+        assert_eq!(stats.correctness, 0.5);
+        assert_eq!(stats.throughput, 2.0 / 3.0);
+
+        let reloaded = Minesweeper::from_json(&ms.to_json()).unwrap();
+        assert_eq!(reloaded.clicks(), stats.clicks);
+        assert_eq!(reloaded.stats().unwrap().ioe, stats.ioe);
+    }
+
+    #[test]
+    fn check_wasted_chords_and_lost_games() {
+        let mut ms = Minesweeper::from_ascii("\
+            * . . *\n\
+            . . . .\n", Rules::default()).unwrap();
+
+        ms.open((1, 1)).unwrap();
+        assert!(matches!(ms.chord((1, 1)), Ok(Chord::FlagMismatch { .. })));
+        assert_eq!(ms.chord((2, 0)).err(), Some(MoveError::NotOpen));
+        ms.open((3, 0)).unwrap();
+
+        let stats = ms.stats().unwrap();
+
+        assert_eq!(stats.clicks, Clicks { left: 2, right: 0, chord: 2, wasted: 2 });
+        assert_eq!(stats.solved_bbbv, 1);
+        assert!(stats.solved_bbbv < stats.bbbv);
+    }
+}